@@ -1,19 +1,36 @@
+//! Cameras and the render loop.
+
+mod aperture;
+mod distortion;
+mod lens;
+mod physical;
+mod projection;
+mod stereo;
+
 use crate::{
-    color::Color,
-    hittable::Hittable,
+    film::Film,
+    hittable::{Hit, Hittable},
     materials::Material,
+    progress::{CancellationToken, ProgressBarObserver, ProgressObserver, Silent, Tracker},
+    random::{self, SampleRng},
     ray::Ray,
-    vector::{random_unit_in_disk, Point3D, Vec3},
+    render_state::{self, RenderState, TileState},
+    tiles::{Tile, TileOrder},
+    vector::{Point3D, Vec3},
     world::{SharedWorld, World},
 };
-use indicatif::ParallelProgressIterator;
-use rand::{
-    distributions::{DistIter, Distribution, Uniform},
-    rngs::ThreadRng,
-    thread_rng,
-};
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use std::io::{self, Write};
+use lens::LensFocus;
+use projection::Basis;
+use rayon::iter::{ParallelBridge, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+pub use aperture::{Aperture, ApertureMask};
+pub use distortion::Distortion;
+pub use lens::Lens;
+pub use physical::PhysicalCamera;
+pub use projection::{CubeFace, CubemapLayout, FisheyeMapping, Projection};
+pub use stereo::{Eye, Stereo, StereoLayout};
 
 impl Default for CameraInfo {
     fn default() -> Self {
@@ -28,7 +45,10 @@ impl Default for CameraInfo {
         )
     }
 }
-#[derive(Clone, Copy)]
+/// Where the camera is, where it looks and the viewport that follows from that.
+/// Only the inputs are serialized, the viewport is recalculated when loading.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "CameraSettings", into = "CameraSettings")]
 pub struct CameraInfo {
     image_height: usize,
     image_width: usize,
@@ -36,6 +56,16 @@ pub struct CameraInfo {
     vfov: f64,
     defocus_angle: f64,
     focus_dist: f64,
+    projection: Projection,
+    stereo: Option<Stereo>,
+    vup: Vec3,
+    roll: f64,
+    physical: Option<PhysicalCamera>,
+    aperture: Aperture,
+    lens: Option<Lens>,
+    distortion: Distortion,
+    shift: [f64; 2],
+    tilt: [f64; 2],
     ///autocalculated values from aboce
     viewport_width: f64,
     viewport_height: f64,
@@ -46,12 +76,14 @@ pub struct CameraInfo {
     pixel00_loc: Vec3,
     viewport_upper_left: Vec3,
     look_at: Vec3,
-    vup: Vec3,
     u_base: Vec3,
     v_base: Vec3,
     w_base: Vec3,
     defocus_disk_u: Vec3,
     defocus_disk_v: Vec3,
+    lens_focus: LensFocus,
+    /// Normal of the plane everything sharp lies on.
+    focus_normal: Vec3,
 }
 impl CameraInfo {
     fn private_new(
@@ -63,51 +95,42 @@ impl CameraInfo {
         focus_dist: f64,
         defocus_angle: f64,
     ) -> Self {
-        let theta = vfov.to_radians();
-        let h = (theta / 2.0).tan();
-        let viewport_height = 2.0 * h * focus_dist;
-        let viewport_width = viewport_height * (image_width as f64 / image_height as f64);
-        let vup = Vec3::new(0.0, 1.0, 0.0);
-        let w_base = (look_from - look_at).unit_vector();
-        let u_base = vup.cross(&w_base).unit_vector();
-        let v_base = w_base.cross(&u_base);
-
-        let viewport_u = viewport_width * u_base;
-        let viewport_v = viewport_height * -v_base;
-
-        let pixel_delta_u = viewport_u / image_width as f64;
-        let pixel_delta_v = viewport_v / image_height as f64;
-
-        let viewport_upper_left =
-            look_from - focus_dist * w_base - &viewport_u * 0.5 - &viewport_v * 0.5;
-        let pixel00_loc = viewport_upper_left + &(pixel_delta_u + pixel_delta_v) * 0.5;
-
-        let defocus_radius = focus_dist * (defocus_angle / 2.0).to_radians().tan();
-        let defocus_disk_u = u_base * defocus_radius;
-        let defocus_disk_v = v_base * defocus_radius;
-        Self {
+        let mut info = Self {
             vfov,
-            u_base,
-            v_base,
-            vup,
-            w_base,
-            viewport_height,
-            viewport_width,
+            u_base: Vec3::ZERO,
+            v_base: Vec3::ZERO,
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            w_base: Vec3::ZERO,
+            viewport_height: 0.0,
+            viewport_width: 0.0,
             camera_center: look_from,
             look_at,
             image_height,
             image_width,
-            viewport_u,
-            viewport_v,
-            pixel_delta_u,
-            pixel00_loc,
-            pixel_delta_v,
-            viewport_upper_left,
+            viewport_u: Vec3::ZERO,
+            viewport_v: Vec3::ZERO,
+            pixel_delta_u: Vec3::ZERO,
+            pixel00_loc: Vec3::ZERO,
+            pixel_delta_v: Vec3::ZERO,
+            viewport_upper_left: Vec3::ZERO,
             defocus_angle,
             focus_dist,
-            defocus_disk_u,
-            defocus_disk_v,
-        }
+            projection: Projection::Perspective,
+            stereo: None,
+            roll: 0.0,
+            physical: None,
+            aperture: Aperture::Disk,
+            lens: None,
+            distortion: Distortion::default(),
+            shift: [0.0, 0.0],
+            tilt: [0.0, 0.0],
+            defocus_disk_u: Vec3::ZERO,
+            defocus_disk_v: Vec3::ZERO,
+            lens_focus: LensFocus::default(),
+            focus_normal: Vec3::ZERO,
+        };
+        info.recalculate();
+        info
     }
 
     pub const fn window_height(&self) -> usize {
@@ -154,58 +177,246 @@ impl CameraInfo {
         &self.viewport_upper_left
     }
 
-    pub fn set_camera_center(&mut self, camera_center: Point3D) {
+    pub const fn set_camera_center(&mut self, camera_center: Point3D) {
         self.camera_center = camera_center;
     }
 
-    pub fn set_look_at(&mut self, look_at: Vec3) {
+    pub const fn set_look_at(&mut self, look_at: Vec3) {
         self.look_at = look_at;
     }
     pub fn recalculate(&mut self) {
-        let theta = self.vfov.to_radians();
-        let h = (theta / 2.0).tan();
-        self.viewport_height = 2.0 * h * self.focus_dist;
-        self.viewport_width =
-            self.viewport_height * (self.image_width as f64 / self.image_height as f64);
-
-        self.w_base = (self.camera_center - self.look_at).unit_vector();
-        self.u_base = self.vup.cross(&self.w_base).unit_vector();
+        let (eye_width, eye_height) = self.eye_size();
+        if let Some(physical) = self.physical {
+            self.vfov = physical.vfov(eye_width as f64 / eye_height as f64);
+            self.defocus_angle = physical.defocus_angle(self.focus_dist);
+        }
+        self.viewport_height = match self.projection {
+            Projection::Orthographic { view_height } => view_height,
+            _ => 2.0 * (self.vfov.to_radians() / 2.0).tan() * self.focus_dist,
+        };
+        self.viewport_width = self.viewport_height * (eye_width as f64 / eye_height as f64);
+
+        self.w_base = self.backwards();
+        let up = self.up_for(&self.w_base);
+        let (sin, cos) = self.roll.to_radians().sin_cos();
+        let unrolled_u = up.cross(&self.w_base).unit_vector();
+        let unrolled_v = self.w_base.cross(&unrolled_u);
+        self.u_base = cos * unrolled_u + sin * unrolled_v;
         self.v_base = self.w_base.cross(&self.u_base);
 
         self.viewport_u = self.viewport_width * self.u_base;
         self.viewport_v = self.viewport_height * -self.v_base;
 
-        self.pixel_delta_u = self.viewport_u / self.image_width as f64;
-        self.pixel_delta_v = self.viewport_v / self.image_height as f64;
+        self.pixel_delta_u = self.viewport_u / eye_width as f64;
+        self.pixel_delta_v = self.viewport_v / eye_height as f64;
 
-        self.viewport_upper_left = self.camera_center
-            - self.focus_dist * self.w_base
-            - &self.viewport_u * 0.5
-            - &self.viewport_v * 0.5;
+        // shifting moves the viewport in its plane, so lines stay as parallel as they are
+        let [shift_x, shift_y] = self.shift;
+        self.viewport_upper_left = self.camera_center - self.focus_dist * self.w_base
+            + self.viewport_u * (shift_x - 0.5)
+            - self.viewport_v * (shift_y + 0.5);
         self.pixel00_loc =
             self.viewport_upper_left + &(self.pixel_delta_u + self.pixel_delta_v) * 0.5;
+        let [tilt, swing] = self.tilt;
+        self.focus_normal = self.w_base
+            + tilt.to_radians().tan() * self.v_base
+            + swing.to_radians().tan() * self.u_base;
         let defocus_radius = self.focus_dist * (self.defocus_angle / 2.0).to_radians().tan();
         self.defocus_disk_u = self.u_base * defocus_radius;
         self.defocus_disk_v = self.v_base * defocus_radius;
+        if let Some(lens) = &self.lens {
+            let (film_width, film_height) = self.film_size();
+            let film_radius = (film_width * film_width + film_height * film_height).sqrt() / 2.0;
+            // lenses are measured in millimeters and scenes in meters
+            self.lens_focus = lens.focus(self.focus_dist * 1000.0, film_radius);
+        }
     }
 
-    pub fn set_focus_dist(&mut self, focus_dist: f64) {
+    /// The unit vector opposite to the view direction, the camera looks down -z
+    /// when it is at the point it looks at.
+    fn backwards(&self) -> Vec3 {
+        let backwards = self.camera_center - self.look_at;
+        match backwards.near_zero() {
+            true => Vec3::new(0.0, 0.0, 1.0),
+            false => backwards.unit_vector(),
+        }
+    }
+
+    /// The up vector, or when looking along it, the first of -z and x that is not
+    /// parallel to the view. Looking straight down then has -z at the top.
+    fn up_for(&self, backwards: &Vec3) -> Vec3 {
+        [
+            self.vup,
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(1.0, 0.0, 0.0),
+        ]
+        .into_iter()
+        .find(|up| up.cross(backwards).length_squared() > 1e-12 * up.length_squared())
+        .unwrap_or(Vec3::new(1.0, 0.0, 0.0))
+    }
+
+    pub const fn set_focus_dist(&mut self, focus_dist: f64) {
         self.focus_dist = focus_dist;
     }
 
-    pub fn set_defocus_angle(&mut self, defocus_angle: f64) {
+    pub const fn set_defocus_angle(&mut self, defocus_angle: f64) {
         self.defocus_angle = defocus_angle;
     }
 
-    pub fn set_vfov(&mut self, vfov: f64) {
+    pub const fn set_vfov(&mut self, vfov: f64) {
         self.vfov = vfov;
     }
 
-    pub fn set_image_height(&mut self, image_height: usize) {
+    pub const fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+    }
+
+    pub const fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    /// The direction that points up in the image, it does not have to be
+    /// perpendicular to the view direction.
+    pub const fn set_vup(&mut self, vup: Vec3) {
+        self.vup = vup;
+    }
+
+    pub const fn vup(&self) -> Vec3 {
+        self.vup
+    }
+
+    /// Rotation around the view direction in degrees, positive rolls the camera
+    /// counter-clockwise.
+    pub const fn set_roll(&mut self, roll: f64) {
+        self.roll = roll;
+    }
+
+    pub const fn roll(&self) -> f64 {
+        self.roll
+    }
+
+    /// Lets real camera settings decide the field of view and depth of field, the
+    /// shutter interval and the exposure.
+    pub const fn set_physical(&mut self, physical: Option<PhysicalCamera>) {
+        self.physical = physical;
+    }
+
+    pub const fn physical(&self) -> Option<PhysicalCamera> {
+        self.physical
+    }
+
+    /// The shape of the lens opening, which out of focus highlights take on.
+    pub fn set_aperture(&mut self, aperture: Aperture) {
+        self.aperture = aperture;
+    }
+
+    pub const fn aperture(&self) -> &Aperture {
+        &self.aperture
+    }
+
+    /// Traces rays through the glass of `lens` instead of the perspective or
+    /// orthographic projection and the thin lens. The film has the size of the
+    /// physical camera's sensor, or 36x24mm, and sits at the camera center.
+    pub fn set_lens(&mut self, lens: Option<Lens>) {
+        self.lens = lens;
+    }
+
+    pub const fn lens(&self) -> Option<&Lens> {
+        self.lens.as_ref()
+    }
+
+    /// Distorts the image of the perspective and orthographic projections, the
+    /// glass of a lens and panoramas are left alone.
+    pub const fn set_distortion(&mut self, distortion: Distortion) {
+        self.distortion = distortion;
+    }
+
+    pub const fn distortion(&self) -> Distortion {
+        self.distortion
+    }
+
+    /// Moves the image across the viewport without turning the camera, by
+    /// fractions of the image width to the right and of its height upwards.
+    /// Vertical lines stay parallel when the camera looks level. A lens is moved
+    /// across the film instead, panoramas are left alone.
+    pub const fn set_shift(&mut self, x: f64, y: f64) {
+        self.shift = [x, y];
+    }
+
+    pub const fn shift(&self) -> [f64; 2] {
+        self.shift
+    }
+
+    /// Turns the plane of focus like a tilting lens does, by `tilt` degrees so it
+    /// gets further away towards the top of the image and by `swing` degrees so
+    /// it gets further away towards the right. It still goes through the point
+    /// `focus_dist` in front of the camera. The glass of a lens and panoramas,
+    /// which have no depth of field, are left alone.
+    pub const fn set_tilt(&mut self, tilt: f64, swing: f64) {
+        self.tilt = [tilt, swing];
+    }
+
+    pub const fn tilt(&self) -> [f64; 2] {
+        self.tilt
+    }
+
+    /// Where a ray from `origin` through `pixel_center` crosses the plane of focus,
+    /// `None` when it does not in front of the camera.
+    fn focus_point(&self, origin: &Vec3, pixel_center: &Vec3) -> Option<Vec3> {
+        if self.tilt == [0.0, 0.0] {
+            return Some(*pixel_center);
+        }
+        let direction = pixel_center - origin;
+        let on_plane = self.camera_center - self.focus_dist * self.w_base;
+        let t = self.focus_normal.dot(&(on_plane - *origin)) / self.focus_normal.dot(&direction);
+        match t > 0.0 && t.is_finite() {
+            true => Some(origin + &(t * direction)),
+            false => None,
+        }
+    }
+
+    /// The part of the sensor the image of one eye covers, in millimeters.
+    fn film_size(&self) -> (f64, f64) {
+        let [width, height] = self
+            .physical
+            .map_or([36.0, 24.0], |physical| physical.sensor());
+        let (eye_width, eye_height) = self.eye_size();
+        let aspect_ratio = eye_width as f64 / eye_height as f64;
+        let used_height = height.min(width / aspect_ratio);
+        (used_height * aspect_ratio, used_height)
+    }
+
+    /// How long the shutter is open in scene time, the rays' times go up to it.
+    pub fn shutter(&self) -> f64 {
+        self.physical.map_or(1.0, |physical| physical.shutter())
+    }
+
+    /// What the gathered radiance gets multiplied by.
+    pub fn exposure(&self) -> f64 {
+        self.physical.map_or(1.0, |physical| physical.exposure())
+    }
+
+    pub const fn set_stereo(&mut self, stereo: Option<Stereo>) {
+        self.stereo = stereo;
+    }
+
+    pub const fn stereo(&self) -> Option<Stereo> {
+        self.stereo
+    }
+
+    /// The size of the image one eye sees, the whole image without stereo.
+    pub const fn eye_size(&self) -> (usize, usize) {
+        match self.stereo {
+            Some(stereo) => stereo.eye_size(self.image_width, self.image_height),
+            None => (self.image_width, self.image_height),
+        }
+    }
+
+    pub const fn set_image_height(&mut self, image_height: usize) {
         self.image_height = image_height;
     }
 
-    pub fn set_image_width(&mut self, image_width: usize) {
+    pub const fn set_image_width(&mut self, image_width: usize) {
         self.image_width = image_width;
     }
     pub fn set_image_height_with_aspect_ratio(&mut self, window_height: usize, aspect_ratio: f64) {
@@ -219,18 +430,172 @@ impl CameraInfo {
         self.image_height = (window_width as f64 * (1.0 / aspect_ratio)) as usize;
     }
 }
+/// The user facing inputs of [`CameraInfo`] and [`Camera`].
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CameraSettings {
+    image_width: usize,
+    /// Derived from `aspect_ratio` when missing, giving both is an error.
+    image_height: Option<usize>,
+    #[serde(skip_serializing)]
+    aspect_ratio: Option<f64>,
+    look_from: Point3D,
+    look_at: Vec3,
+    vup: Vec3,
+    roll: f64,
+    vfov: f64,
+    defocus_angle: f64,
+    focus_dist: f64,
+    #[serde(skip_serializing_if = "Projection::is_perspective")]
+    projection: Projection,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stereo: Option<Stereo>,
+    /// Overrides `vfov` and `defocus_angle` when there.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    physical: Option<PhysicalCamera>,
+    #[serde(skip_serializing_if = "Aperture::is_disk")]
+    aperture: Aperture,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lens: Option<Lens>,
+    #[serde(skip_serializing_if = "Distortion::is_none")]
+    distortion: Distortion,
+    /// Horizontal and vertical lens shift.
+    #[serde(skip_serializing_if = "is_zero")]
+    shift: [f64; 2],
+    /// Tilt and swing of the plane of focus in degrees.
+    #[serde(skip_serializing_if = "is_zero")]
+    tilt: [f64; 2],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    samples_per_pixel: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_depth: Option<usize>,
+}
+fn is_zero(pair: &[f64; 2]) -> bool {
+    *pair == [0.0, 0.0]
+}
+impl Default for CameraSettings {
+    fn default() -> Self {
+        let mut settings: Self = CameraInfo::default().into();
+        settings.image_height = None;
+        settings
+    }
+}
+impl From<CameraInfo> for CameraSettings {
+    fn from(value: CameraInfo) -> Self {
+        Self {
+            image_width: value.image_width,
+            image_height: Some(value.image_height),
+            aspect_ratio: None,
+            look_from: value.camera_center,
+            look_at: value.look_at,
+            vup: value.vup,
+            roll: value.roll,
+            vfov: value.vfov,
+            defocus_angle: value.defocus_angle,
+            focus_dist: value.focus_dist,
+            projection: value.projection,
+            stereo: value.stereo,
+            physical: value.physical,
+            aperture: value.aperture,
+            lens: value.lens,
+            distortion: value.distortion,
+            shift: value.shift,
+            tilt: value.tilt,
+            samples_per_pixel: None,
+            max_depth: None,
+        }
+    }
+}
+impl TryFrom<CameraSettings> for CameraInfo {
+    type Error = String;
+
+    fn try_from(value: CameraSettings) -> Result<Self, Self::Error> {
+        if value.image_height.is_some() && value.aspect_ratio.is_some() {
+            return Err("give either image_height or aspect_ratio, not both".to_string());
+        }
+        let image_height = value.image_height.unwrap_or_else(|| {
+            let eye_aspect_ratio = value.projection.aspect_ratio();
+            let projection_aspect_ratio = match value.stereo {
+                Some(stereo) => eye_aspect_ratio.map(|ratio| stereo.image_aspect_ratio(ratio)),
+                None => eye_aspect_ratio,
+            };
+            let aspect_ratio = value.aspect_ratio.or(projection_aspect_ratio);
+            (value.image_width as f64 / aspect_ratio.unwrap_or(16.0 / 9.0)) as usize
+        });
+        let mut info = Self::private_new(
+            image_height,
+            value.image_width,
+            value.look_from,
+            value.look_at,
+            value.vfov,
+            value.focus_dist,
+            value.defocus_angle,
+        );
+        info.set_projection(value.projection);
+        info.set_stereo(value.stereo);
+        info.set_vup(value.vup);
+        info.set_roll(value.roll);
+        info.set_physical(value.physical);
+        info.set_aperture(value.aperture);
+        info.set_lens(value.lens);
+        info.set_distortion(value.distortion);
+        info.set_shift(value.shift[0], value.shift[1]);
+        info.set_tilt(value.tilt[0], value.tilt[1]);
+        info.recalculate();
+        Ok(info)
+    }
+}
+impl From<Camera> for CameraSettings {
+    fn from(value: Camera) -> Self {
+        Self {
+            samples_per_pixel: Some(value.samples_per_pixel),
+            max_depth: Some(value.max_depth),
+            ..value.viewport.into()
+        }
+    }
+}
+impl TryFrom<CameraSettings> for Camera {
+    type Error = String;
+
+    fn try_from(value: CameraSettings) -> Result<Self, Self::Error> {
+        let default = Camera::default();
+        Ok(Self {
+            samples_per_pixel: value.samples_per_pixel.unwrap_or(default.samples_per_pixel),
+            max_depth: value.max_depth.unwrap_or(default.max_depth),
+            viewport: value.try_into()?,
+            ..default
+        })
+    }
+}
 impl Default for Camera {
     fn default() -> Self {
         Self {
             viewport: Default::default(),
             samples_per_pixel: 20,
+            max_depth: 50,
+            aovs: false,
+            seed: None,
+            verbose: true,
+            tile_size: 32,
+            tile_order: TileOrder::Scanline,
         }
     }
 }
-#[derive(Clone)]
+/// Looks at a [`World`] and renders it, see [`Camera::render`].
+///
+/// Serialized like [`CameraInfo`] with the samples per pixel and max depth next to
+/// it, the render options that do not change the picture are left out.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "CameraSettings", into = "CameraSettings")]
 pub struct Camera {
     viewport: CameraInfo,
     samples_per_pixel: usize,
+    max_depth: usize,
+    aovs: bool,
+    seed: Option<u64>,
+    verbose: bool,
+    tile_size: usize,
+    tile_order: TileOrder,
 }
 
 impl Camera {
@@ -238,6 +603,60 @@ impl Camera {
         self.viewport.image_width * self.viewport.image_height
     }
 
+    pub const fn image_width(&self) -> usize {
+        self.viewport.image_width
+    }
+
+    pub const fn image_height(&self) -> usize {
+        self.viewport.image_height
+    }
+
+    pub const fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel
+    }
+
+    pub const fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// How many times a ray may bounce before it stops gathering light.
+    pub const fn set_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Renders with the same seed come out identical, without one every render differs.
+    pub const fn set_seed(mut self, seed: Option<u64>) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Width and height of the square tiles the image is rendered in.
+    pub const fn set_tile_size(mut self, tile_size: usize) -> Self {
+        self.tile_size = tile_size;
+        self
+    }
+
+    /// The order tiles are rendered in, it does not change the finished image.
+    pub const fn set_tile_order(mut self, tile_order: TileOrder) -> Self {
+        self.tile_order = tile_order;
+        self
+    }
+
+    pub const fn tile_size(&self) -> usize {
+        self.tile_size
+    }
+
+    pub const fn tile_order(&self) -> TileOrder {
+        self.tile_order
+    }
+
+    /// Whether to print progress and status messages to stderr.
+    pub const fn set_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
     pub fn set_camera_center(mut self, camera_center: Point3D) -> Self {
         self.viewport.set_camera_center(camera_center);
         self.viewport.recalculate();
@@ -250,12 +669,34 @@ impl Camera {
         self
     }
 
+    pub fn set_vup(mut self, vup: Vec3) -> Self {
+        self.viewport.set_vup(vup);
+        self.viewport.recalculate();
+        self
+    }
+
+    pub fn set_roll(mut self, roll: f64) -> Self {
+        self.viewport.set_roll(roll);
+        self.viewport.recalculate();
+        self
+    }
+
     pub fn set_samples_per_pixel(mut self, samples_per_pixel: usize) -> Self {
         self.samples_per_pixel = samples_per_pixel;
         self.viewport.recalculate();
         self
     }
 
+    /// Also gather depth, normal, albedo and object id buffers while rendering.
+    pub const fn set_aovs(mut self, aovs: bool) -> Self {
+        self.aovs = aovs;
+        self
+    }
+
+    pub const fn aovs(&self) -> bool {
+        self.aovs
+    }
+
     pub fn set_defocus_angle(mut self, defocus_angle: f64) -> Self {
         self.viewport.set_defocus_angle(defocus_angle);
         self.viewport.recalculate();
@@ -307,65 +748,357 @@ impl Camera {
         self.viewport.recalculate();
         self
     }
-    pub fn render(&self, world: &World) {
-        let pixel_samples_scale = 1.0 / self.samples_per_pixel as f64;
-        eprintln!("pix/samp scale:{}", pixel_samples_scale);
-        let shared_world: SharedWorld = world.into();
-        let s = (0..self.viewport.image_height)
-            .into_par_iter()
-            .progress()
-            .map(|h| {
-                let mut rng = Uniform::new(0.0, 1.0).sample_iter(thread_rng());
-                (0..self.viewport.image_width).fold(String::new(), |s, w| {
-                    let mut pixel_color = Vec3::new(0., 0., 0.);
-                    for _ in 0..self.samples_per_pixel {
-                        let r: Ray = self.viewport.get_sample_ray(w, h, &mut rng);
-                        let f = ray_color(r, 50, &shared_world);
-                        pixel_color += f;
-                    }
-                    let pixel_color = Color::new(pixel_color * pixel_samples_scale);
-                    s + &format!("{}\n", pixel_color)
-                })
-            })
-            .reduce(String::new, |acc, v| acc + &v);
-        let stdout = io::stdout();
-        let mut stdout_handle = stdout.lock();
-        write!(
-            stdout_handle,
-            "P3\n{} {}\n255\n",
-            self.viewport.image_width, self.viewport.image_height
+
+    pub fn set_projection(mut self, projection: Projection) -> Self {
+        self.viewport.set_projection(projection);
+        self.viewport.recalculate();
+        self
+    }
+
+    pub fn set_physical(mut self, physical: Option<PhysicalCamera>) -> Self {
+        self.viewport.set_physical(physical);
+        self.viewport.recalculate();
+        self
+    }
+
+    pub fn set_aperture(mut self, aperture: Aperture) -> Self {
+        self.viewport.set_aperture(aperture);
+        self
+    }
+
+    pub fn set_lens(mut self, lens: Option<Lens>) -> Self {
+        self.viewport.set_lens(lens);
+        self.viewport.recalculate();
+        self
+    }
+
+    pub const fn set_distortion(mut self, distortion: Distortion) -> Self {
+        self.viewport.set_distortion(distortion);
+        self
+    }
+
+    pub fn set_shift(mut self, x: f64, y: f64) -> Self {
+        self.viewport.set_shift(x, y);
+        self.viewport.recalculate();
+        self
+    }
+
+    pub fn set_tilt(mut self, tilt: f64, swing: f64) -> Self {
+        self.viewport.set_tilt(tilt, swing);
+        self.viewport.recalculate();
+        self
+    }
+
+    /// Renders both eyes of `stereo` into the image, each into its half.
+    pub fn set_stereo(mut self, stereo: Option<Stereo>) -> Self {
+        self.viewport.set_stereo(stereo);
+        self.viewport.recalculate();
+        self
+    }
+
+    /// The faces of a film rendered by this camera, when it renders a cubemap whose
+    /// faces go into separate images. Stereo cubemaps are kept in one image.
+    pub fn cubemap_faces(&self, film: &Film) -> Option<Vec<(CubeFace, Film)>> {
+        let (
+            Projection::Cubemap {
+                layout: CubemapLayout::Separate,
+            },
+            None,
+        ) = (self.viewport.projection, self.viewport.stereo)
+        else {
+            return None;
+        };
+        let size = (film.width() / CubeFace::ALL.len()).min(film.height());
+        let faces = CubeFace::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, face)| (*face, film.crop(i * size, 0, size, size)))
+            .collect();
+        Some(faces)
+    }
+
+    /// Traces every pixel of the image on all rayon threads, with a progress bar
+    /// when verbose.
+    pub fn render(&self, world: &World) -> Film {
+        let observer: &dyn ProgressObserver = match self.verbose {
+            true => &ProgressBarObserver::new(),
+            false => &Silent,
+        };
+        self.render_with(world, observer, &CancellationToken::new())
+    }
+
+    /// Reports every finished tile to `observer` and stops early once `cancel` is
+    /// cancelled. Tiles that were not rendered by then are left black in the film.
+    pub fn render_with(
+        &self,
+        world: &World,
+        observer: &dyn ProgressObserver,
+        cancel: &CancellationToken,
+    ) -> Film {
+        let state = self.render_state();
+        self.render_into(world, &state, observer, cancel);
+        state.film()
+    }
+
+    /// An empty render of this camera's image, seeded and split into tiles.
+    pub fn render_state(&self) -> RenderState {
+        let (width, height) = (self.viewport.image_width, self.viewport.image_height);
+        RenderState::new(
+            width,
+            height,
+            self.seed.unwrap_or_else(random::entropy_seed),
+            self.aovs,
+            self.tile_order.tiles(width, height, self.tile_size),
         )
-        .expect("can not write to stdout");
-        eprintln!("writing to stdout");
-        println!("{}", s);
-        eprintln!("Done");
+    }
+
+    /// Renders the whole image in passes of `pass_samples` samples per pixel, until
+    /// the samples per pixel of this camera are reached or `cancel` is cancelled,
+    /// for example by its deadline. `after_pass` sees the state after every pass,
+    /// including a last one that was cut short, and can stop the render by
+    /// returning an error.
+    pub fn render_progressive<E>(
+        &self,
+        world: &World,
+        state: &RenderState,
+        pass_samples: usize,
+        observer: &dyn ProgressObserver,
+        cancel: &CancellationToken,
+        mut after_pass: impl FnMut(&RenderState) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let mut done = state.samples_per_pixel();
+        while done < self.samples_per_pixel && !cancel.is_cancelled() {
+            let target = (done + pass_samples.max(1)).min(self.samples_per_pixel);
+            let pass = self.clone().set_samples_per_pixel(target);
+            pass.render_into(world, state, observer, cancel);
+            after_pass(state)?;
+            done = state.samples_per_pixel();
+        }
+        Ok(())
+    }
+
+    /// Adds samples to every tile of `state` until it has the samples per pixel of
+    /// this camera, using the seed and tiles of `state`. `state` may be shared with
+    /// another thread meanwhile, for example to save checkpoints.
+    pub fn render_into(
+        &self,
+        world: &World,
+        state: &RenderState,
+        observer: &dyn ProgressObserver,
+        cancel: &CancellationToken,
+    ) {
+        let (width, height) = (self.viewport.image_width, self.viewport.image_height);
+        assert!(
+            state.width() == width && state.height() == height,
+            "a {}x{} render cannot be continued at {}x{}",
+            state.width(),
+            state.height(),
+            width,
+            height
+        );
+        if self.verbose {
+            eprintln!(
+                "rendering {} spp, {} spp done already",
+                self.samples_per_pixel,
+                state.samples_per_pixel()
+            );
+        }
+        let shared_world: SharedWorld = world.into();
+        let seed = state.seed();
+        let tracker = Tracker::start(observer, state.tiles().len());
+        // tiles are handed out in order as threads become free
+        state
+            .tiles()
+            .iter()
+            .par_bridge()
+            .filter(|_| !cancel.is_cancelled())
+            .for_each(|tile_state| {
+                let (tile, first_sample) = {
+                    let tile_state = render_state::lock(tile_state);
+                    (tile_state.tile, tile_state.samples)
+                };
+                let target = self.samples_per_pixel.max(first_sample);
+                let rendered = self.render_tile(
+                    &shared_world,
+                    seed,
+                    tile,
+                    first_sample..target,
+                    state.has_aovs(),
+                );
+                render_state::lock(tile_state).merge(rendered);
+                tracker.add((tile.area() * (target - first_sample)) as u64);
+            });
+        let progress = tracker.finish();
+        if self.verbose {
+            match progress.is_complete() {
+                true => eprintln!("Done"),
+                false => eprintln!(
+                    "Cancelled after {} of {} tiles",
+                    progress.done, progress.total
+                ),
+            }
+        }
+    }
+
+    /// The sums of the samples with indices in `samples` of every pixel of `tile`.
+    pub(crate) fn render_tile(
+        &self,
+        world: &SharedWorld,
+        seed: u64,
+        tile: Tile,
+        samples: Range<usize>,
+        aovs: bool,
+    ) -> TileState {
+        let mut sums = TileState::empty(tile, samples.end, aovs);
+        let exposure = self.viewport.exposure();
+        for (i, (w, h)) in tile.pixels().enumerate() {
+            let pixel_seed = random::derive_seed(seed, (h * self.viewport.image_width + w) as u64);
+            for sample in samples.clone() {
+                // seeded per sample so tiles, passes and resuming do not change the image
+                let sample_seed = random::derive_seed(pixel_seed, sample as u64);
+                random::reseed(sample_seed);
+                let mut rng = random::sample_rng(random::derive_seed(sample_seed, 0));
+                let Some((r, weight)) = self.viewport.get_sample_ray(w, h, &mut rng) else {
+                    continue;
+                };
+                // the first hit is shared by the aovs and the path
+                let first_hit = world.hit_with_index(&r);
+                if let Some(aov) = sums.aovs.get_mut(i) {
+                    aov.add(&r, first_hit.as_ref(), world);
+                }
+                let first_hit = first_hit.map(|(_, hit)| hit);
+                sums.color[i] +=
+                    (&shade(r, first_hit, self.max_depth as isize, world) * &weight) * exposure;
+            }
+        }
+        sums
     }
 }
 impl CameraInfo {
-    pub fn get_sample_ray(
+    /// A ray through pixel (`w`, `h`) and what its light counts for. `None` for the
+    /// parts of the image a projection does not cover and rays a lens blocks.
+    pub(crate) fn get_sample_ray(
         &self,
         w: usize,
         h: usize,
-        rng: &mut DistIter<Uniform<f64>, ThreadRng, f64>,
-    ) -> Ray {
+        rng: &mut SampleRng,
+    ) -> Option<(Ray, Vec3)> {
         let offset = sample_square(rng);
-        let pixel_center = self.pixel00_loc()
-            + &((w as f64 + offset.x()) * self.pixel_delta_u())
-            + ((h as f64 + offset.y()) * self.pixel_delta_v());
-        let ray_origin = match self.defocus_angle <= 0.0 {
-            true => self.camera_center(),
-            false => self.defocus_disk_sample(),
+        // how far right of the center the eye is and how much its view is shifted
+        let (eye, parallax, w, h) = match self.stereo {
+            Some(stereo) => {
+                let (eye, w, h) = stereo.eye_pixel(w, h, self.image_width, self.image_height)?;
+                let parallax = match stereo.convergence() {
+                    Some(convergence) => 1.0 - self.focus_dist / convergence,
+                    None => 1.0,
+                };
+                (stereo.eye_offset(eye), parallax, w, h)
+            }
+            None => (0.0, 0.0, w, h),
+        };
+        let (ray_origin, ray_direction, weight) = match (&self.lens, self.projection.is_panoramic())
+        {
+            // panoramas have no single viewport and no depth of field
+            (_, true) => {
+                let basis = Basis {
+                    u: self.u_base,
+                    v: self.v_base,
+                    w: self.w_base,
+                };
+                let (eye_width, eye_height) = self.eye_size();
+                let direction = self
+                    .projection
+                    .direction(
+                        &basis,
+                        w as f64 + 0.5 + offset.x(),
+                        h as f64 + 0.5 + offset.y(),
+                        eye_width as f64,
+                        eye_height as f64,
+                    )?
+                    .unit_vector();
+                match self.stereo {
+                    Some(stereo) => {
+                        // the eye circles the center to stay beside the direction it
+                        // looks in, moving in towards the poles where there is no beside
+                        let beside = direction.cross(&self.v_base);
+                        let origin = self.camera_center + eye * beside;
+                        let direction = match stereo.convergence() {
+                            Some(convergence) => convergence * direction - eye * beside,
+                            None => direction,
+                        };
+                        (origin, direction, Vec3::new(1.0, 1.0, 1.0))
+                    }
+                    None => (self.camera_center, direction, Vec3::new(1.0, 1.0, 1.0)),
+                }
+            }
+            (Some(lens), false) => {
+                let (eye_width, eye_height) = self.eye_size();
+                let (film_width, film_height) = self.film_size();
+                // the lens turns the image upside down, so the film is too, and
+                // shifting the lens one way is moving the film the other way
+                let [shift_x, shift_y] = self.shift;
+                let x =
+                    (0.5 - shift_x - (w as f64 + 0.5 + offset.x()) / eye_width as f64) * film_width;
+                let y = ((h as f64 + 0.5 + offset.y()) / eye_height as f64 - 0.5 - shift_y)
+                    * film_height;
+                let ray = lens.sample_ray(&self.lens_focus, x, y)?;
+                let to_world =
+                    |v: Vec3| *v.x() * self.u_base + *v.y() * self.v_base - *v.z() * self.w_base;
+                let origin = self.camera_center + eye * self.u_base + to_world(ray.origin) / 1000.0;
+                let weight = Vec3::new(ray.weight, ray.weight, ray.weight);
+                (origin, to_world(ray.direction), weight)
+            }
+            (None, false) => {
+                let pixel_center = self.pixel00_loc()
+                    + &((w as f64 + offset.x()) * self.pixel_delta_u())
+                    + ((h as f64 + offset.y()) * self.pixel_delta_v());
+                let (pixel_center, weight) = match self.distortion.is_none() {
+                    true => (pixel_center, Vec3::new(1.0, 1.0, 1.0)),
+                    false => self.undistort(pixel_center),
+                };
+                let lens = match self.defocus_angle <= 0.0 {
+                    true => Vec3::ZERO,
+                    false => self.defocus_disk_sample(),
+                };
+                let eye_shift = eye * self.u_base;
+                let center = match self.projection {
+                    // straight back from the pixel onto the plane of the camera
+                    Projection::Orthographic { .. } => pixel_center + self.focus_dist * self.w_base,
+                    _ => self.camera_center(),
+                };
+                let origin = center + lens;
+                let direction = match self.focus_point(&center, &pixel_center) {
+                    Some(focus_point) => focus_point + parallax * eye_shift - (origin + eye_shift),
+                    // focused beyond infinity, every point of the lens looks the same way
+                    None => pixel_center - center,
+                };
+                (origin + eye_shift, direction, weight)
+            }
         };
-        let ray_direction = pixel_center - ray_origin;
 
-        Ray::new(ray_origin, ray_direction, rng.next().unwrap_or_default())
+        let time = rng.next().unwrap_or_default() * self.shutter();
+        Some((Ray::new(ray_origin, ray_direction, time), weight))
+    }
+    /// The point of the viewport an undistorted image shows where `pixel_center`
+    /// is, for the color channel that the weight picks.
+    fn undistort(&self, pixel_center: Vec3) -> (Vec3, Vec3) {
+        let (scale, weight) = self.distortion.sample_channel();
+        let viewport_center = self.camera_center - self.focus_dist * self.w_base;
+        let offset = (pixel_center - viewport_center) / (self.focus_dist * scale);
+        let (x, y) = self
+            .distortion
+            .undistort(offset.dot(&self.u_base), -offset.dot(&self.v_base));
+        let undistorted = viewport_center + self.focus_dist * (x * self.u_base - y * self.v_base);
+        (undistorted, weight)
     }
+
+    /// An offset from the center of the lens.
     fn defocus_disk_sample(&self) -> Vec3 {
-        let p = random_unit_in_disk();
-        self.camera_center + (*p.x() * self.defocus_disk_u) + (*p.y() * self.defocus_disk_v)
+        let p = self.aperture.sample();
+        (*p.x() * self.defocus_disk_u) + (*p.y() * self.defocus_disk_v)
     }
 }
-pub fn sample_square(rng: &mut DistIter<Uniform<f64>, ThreadRng, f64>) -> Vec3 {
+pub(crate) fn sample_square(rng: &mut SampleRng) -> Vec3 {
     Vec3::new(
         rng.next().unwrap_or_default() - 0.5,
         rng.next().unwrap_or_default() - 0.5,
@@ -377,14 +1110,21 @@ fn ray_color<'a>(r: Ray, depth: isize, hittable: &'a SharedWorld<'a>) -> Vec3 {
     if depth <= 0 {
         return Vec3::new(0., 0., 0.);
     }
-    if let Some(hit) = hittable.hit(&r) {
+    let hit = hittable.hit(&r);
+    shade(r, hit, depth, hittable)
+}
+
+/// The light coming back along `r` given where it hits, if anywhere.
+fn shade<'a>(r: Ray, hit: Option<Hit>, depth: isize, hittable: &'a SharedWorld<'a>) -> Vec3 {
+    if depth <= 0 {
+        return Vec3::new(0., 0., 0.);
+    }
+    if let Some(hit) = hit {
+        let emitted = hit.mat.emitted(&hit);
         if let Some((scatterd, color)) = hit.mat.scatter(&r, &hit) {
-            return color.vec3() * &ray_color(scatterd, depth - 1, hittable);
+            return emitted + color.vec3() * &ray_color(scatterd, depth - 1, hittable);
         }
-        return Vec3::ZERO;
+        return emitted;
     }
-    let unit_direction = r.direction().unit_vector();
-    let a = 0.5 * (unit_direction.y() + 1.0);
-
-    (1.0 - a) * Vec3::new(1.0, 1.0, 1.0) + a * Vec3::new(0.5, 0.7, 1.0)
+    hittable.environment.color(&r)
 }
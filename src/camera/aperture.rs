@@ -0,0 +1,181 @@
+use std::{
+    f64::consts::PI,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    random,
+    vector::{random_unit_in_disk, Vec3},
+};
+
+/// The shape of the lens opening, which is the shape out of focus highlights take.
+/// Every shape spans the unit disk that `defocus_angle` sizes.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Aperture {
+    #[default]
+    Disk,
+    /// A regular polygon of `blades` straight blades, with a corner at the top
+    /// until it is turned by `rotation` degrees. Fewer than 3 blades make a disk.
+    Polygon {
+        blades: u32,
+        #[serde(default)]
+        rotation: f64,
+    },
+    /// A grayscale image of the opening, see [`ApertureMask`].
+    Image(ApertureMask),
+}
+
+impl Aperture {
+    pub const fn is_disk(&self) -> bool {
+        matches!(self, Aperture::Disk)
+    }
+
+    /// A random point of the opening, in the xy plane.
+    pub fn sample(&self) -> Vec3 {
+        match self {
+            Aperture::Polygon { blades, rotation } if *blades >= 3 => {
+                let (blade, a, b) =
+                    random::with_rng(|rng| (rng.gen_range(0..*blades), rng.gen(), rng.gen()));
+                // the triangle between the center and the edge of one blade
+                let angle = |corner: u32| {
+                    PI / 2.0 + rotation.to_radians() + 2.0 * PI * corner as f64 / *blades as f64
+                };
+                let (first, second) = (angle(blade), angle(blade + 1));
+                let (a, b): (f64, f64) = match a + b > 1.0 {
+                    true => (1.0 - a, 1.0 - b),
+                    false => (a, b),
+                };
+                Vec3::new(
+                    a * first.cos() + b * second.cos(),
+                    a * first.sin() + b * second.sin(),
+                    0.0,
+                )
+            }
+            Aperture::Image(mask) => mask.sample(),
+            _ => random_unit_in_disk(),
+        }
+    }
+}
+
+/// An aperture read from a grayscale png, where white lets all light through and
+/// black none. The image is centered on the lens and its longer side spans the
+/// diameter of the unit disk, color images use the average of their channels.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "MaskSettings", into = "MaskSettings")]
+pub struct ApertureMask {
+    path: PathBuf,
+    width: usize,
+    height: usize,
+    /// Running sums of the pixel values, ending in 1.
+    cdf: Arc<[f64]>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaskSettings {
+    path: PathBuf,
+}
+
+impl ApertureMask {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let (width, height, values) =
+            read_grayscale(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut sum = 0.0;
+        let mut cdf: Vec<f64> = values
+            .iter()
+            .map(|value| {
+                sum += value;
+                sum
+            })
+            .collect();
+        if sum <= 0.0 {
+            return Err(format!("{} lets no light through", path.display()));
+        }
+        cdf.iter_mut().for_each(|c| *c /= sum);
+        Ok(Self {
+            path: path.to_path_buf(),
+            width,
+            height,
+            cdf: cdf.into(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Picks a pixel in proportion to its value and a point within it.
+    fn sample(&self) -> Vec3 {
+        let (pick, dx, dy): (f64, f64, f64) =
+            random::with_rng(|rng| (rng.gen(), rng.gen(), rng.gen()));
+        let index = self
+            .cdf
+            .partition_point(|c| *c <= pick)
+            .min(self.cdf.len() - 1);
+        let (column, row) = (index % self.width, index / self.width);
+        let size = self.width.max(self.height) as f64;
+        Vec3::new(
+            (2.0 * (column as f64 + dx) - self.width as f64) / size,
+            (self.height as f64 - 2.0 * (row as f64 + dy)) / size,
+            0.0,
+        )
+    }
+}
+
+impl PartialEq for ApertureMask {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+
+impl TryFrom<MaskSettings> for ApertureMask {
+    type Error = String;
+
+    fn try_from(value: MaskSettings) -> Result<Self, Self::Error> {
+        Self::load(crate::scene::scene_file(&value.path)?)
+    }
+}
+
+impl From<ApertureMask> for MaskSettings {
+    fn from(value: ApertureMask) -> Self {
+        Self { path: value.path }
+    }
+}
+
+/// Width, height and the 0-1 values of the pixels of a png, row by row.
+fn read_grayscale(path: &Path) -> Result<(usize, usize, Vec<f64>), png::DecodingError> {
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0; reader.output_buffer_size().unwrap_or_default()];
+    let info = reader.next_frame(&mut buffer)?;
+    let channels = info.color_type.samples();
+    // the alpha channel, if any, scales the gray value
+    let color_channels = match info.color_type {
+        png::ColorType::GrayscaleAlpha | png::ColorType::Rgba => channels - 1,
+        _ => channels,
+    };
+    let values = buffer[..info.buffer_size()]
+        .chunks_exact(channels)
+        .map(|pixel| {
+            let gray = pixel[..color_channels]
+                .iter()
+                .map(|c| *c as f64 / 255.0)
+                .sum::<f64>()
+                / color_channels as f64;
+            match color_channels < channels {
+                true => gray * pixel[channels - 1] as f64 / 255.0,
+                false => gray,
+            }
+        })
+        .collect();
+    Ok((info.width as usize, info.height as usize, values))
+}
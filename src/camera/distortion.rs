@@ -0,0 +1,98 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{random, vector::Vec3};
+
+/// Brown–Conrady lens distortion and lateral chromatic aberration, for matching
+/// the look of real photographs.
+///
+/// Coordinates are the ones of OpenCV's camera calibration, x to the right and
+/// y down from the image center, divided by the focal length. Calibrated
+/// coefficients can be used as they are.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Distortion {
+    /// k1, k2 and k3, negative for barrel and positive for pincushion distortion.
+    radial: [f64; 3],
+    /// p1 and p2, for lenses not quite parallel to the sensor.
+    tangential: [f64; 2],
+    /// How much larger the red and the blue image are than the green one, 0.002
+    /// makes red 0.2% larger.
+    chromatic_aberration: [f64; 2],
+}
+
+impl Distortion {
+    pub const fn set_radial(mut self, k1: f64, k2: f64, k3: f64) -> Self {
+        self.radial = [k1, k2, k3];
+        self
+    }
+
+    pub const fn set_tangential(mut self, p1: f64, p2: f64) -> Self {
+        self.tangential = [p1, p2];
+        self
+    }
+
+    pub const fn set_chromatic_aberration(mut self, red: f64, blue: f64) -> Self {
+        self.chromatic_aberration = [red, blue];
+        self
+    }
+
+    pub const fn radial(&self) -> [f64; 3] {
+        self.radial
+    }
+
+    pub const fn tangential(&self) -> [f64; 2] {
+        self.tangential
+    }
+
+    pub const fn chromatic_aberration(&self) -> [f64; 2] {
+        self.chromatic_aberration
+    }
+
+    pub fn is_none(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Where the lens puts the point that an ideal lens puts at (`x`, `y`).
+    pub fn distort(&self, x: f64, y: f64) -> (f64, f64) {
+        let [k1, k2, k3] = self.radial;
+        let [p1, p2] = self.tangential;
+        let r2 = x * x + y * y;
+        let radial = 1.0 + r2 * (k1 + r2 * (k2 + r2 * k3));
+        (
+            x * radial + 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x),
+            y * radial + p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y,
+        )
+    }
+
+    /// The point an ideal lens would show at (`x`, `y`), found by iterating
+    /// [`Distortion::distort`] backwards.
+    pub fn undistort(&self, x: f64, y: f64) -> (f64, f64) {
+        let [k1, k2, k3] = self.radial;
+        let [p1, p2] = self.tangential;
+        let (mut ux, mut uy) = (x, y);
+        for _ in 0..20 {
+            let r2 = ux * ux + uy * uy;
+            let radial = 1.0 + r2 * (k1 + r2 * (k2 + r2 * k3));
+            let dx = 2.0 * p1 * ux * uy + p2 * (r2 + 2.0 * ux * ux);
+            let dy = p1 * (r2 + 2.0 * uy * uy) + 2.0 * p2 * ux * uy;
+            (ux, uy) = ((x - dx) / radial, (y - dy) / radial);
+        }
+        (ux, uy)
+    }
+
+    /// Picks the color channel a camera ray carries, as how much larger the image
+    /// of that channel is and the weight that makes up for the other channels.
+    /// Without chromatic aberration every ray carries all of them.
+    pub(crate) fn sample_channel(&self) -> (f64, Vec3) {
+        let [red, blue] = self.chromatic_aberration;
+        if red == 0.0 && blue == 0.0 {
+            return (1.0, Vec3::new(1.0, 1.0, 1.0));
+        }
+        match random::with_rng(|rng| rng.gen_range(0..3)) {
+            0 => (1.0 + red, Vec3::new(3.0, 0.0, 0.0)),
+            1 => (1.0, Vec3::new(0.0, 3.0, 0.0)),
+            _ => (1.0 + blue, Vec3::new(0.0, 0.0, 3.0)),
+        }
+    }
+}
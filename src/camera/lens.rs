@@ -0,0 +1,344 @@
+use std::{
+    f64::consts::PI,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{random, vector::Vec3};
+
+/// A lens made of spherical glass surfaces one after another, traced ray by ray
+/// from the film out into the scene. Vignetting, distortion and the field of
+/// view changing with focus all come from the glass.
+///
+/// Prescriptions are text tables with one surface per line, from the front of
+/// the lens to the back: the radius of curvature, the thickness up to the next
+/// surface, the index of refraction behind the surface and the diameter, all
+/// lengths in millimeters. A radius of 0 is the aperture stop, an index of 0 or
+/// 1 air, and `#` starts a comment. See `lenses/` for examples.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "LensSettings", into = "LensSettings")]
+pub struct Lens {
+    path: PathBuf,
+    aperture: Option<f64>,
+    /// From front to back, with the last surface at 0 and the film behind it.
+    surfaces: Arc<[Surface]>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LensSettings {
+    path: PathBuf,
+    /// Diameter of the aperture stop in millimeters, the prescription's when missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    aperture: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Surface {
+    /// Position of the vertex on the optical axis.
+    z: f64,
+    /// Zero for the flat aperture stop.
+    radius: f64,
+    aperture_radius: f64,
+    ior_front: f64,
+    ior_back: f64,
+}
+
+/// Where the film has to be for the lens to focus at some distance, and how to
+/// sample rays through it there.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct LensFocus {
+    film_distance: f64,
+    /// The part of the back surface light from the film gets through.
+    pupil_radius: f64,
+    /// What makes the image center come out as bright as with a thin lens.
+    normalization: f64,
+}
+
+/// Light from the scene in millimeters, along the optical axis the lens looks down.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct LensRay {
+    pub origin: Vec3,
+    pub direction: Vec3,
+    pub weight: f64,
+}
+
+impl Lens {
+    pub fn load(path: impl AsRef<Path>, aperture: Option<f64>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let source =
+            std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let surfaces = parse(&source, aperture).map_err(|e| format!("{}:{}", path.display(), e))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            aperture,
+            surfaces: surfaces.into(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The diameter of the aperture stop, if it was changed from the prescription's.
+    pub const fn aperture(&self) -> Option<f64> {
+        self.aperture
+    }
+
+    /// The effective focal length in millimeters.
+    pub fn focal_length(&self) -> f64 {
+        let (_, _, rear_focal_point, rear_principal_plane) = self.cardinal_points();
+        rear_principal_plane - rear_focal_point
+    }
+
+    /// Moves the film to focus at `distance` millimeters in front of it, or as close
+    /// as the lens goes, for a film whose corners are `film_radius` from the center.
+    pub(crate) fn focus(&self, distance: f64, film_radius: f64) -> LensFocus {
+        let (_, front_principal_plane, rear_focal_point, rear_principal_plane) =
+            self.cardinal_points();
+        let focal_length = rear_principal_plane - rear_focal_point;
+        // thick lens equation, with the principal planes moving along with the film
+        let a = distance - front_principal_plane;
+        let b = rear_principal_plane;
+        let discriminant = ((a + b) * (a + b - 4.0 * focal_length)).max(0.0);
+        let film_distance = ((a - b) - discriminant.sqrt()) / 2.0;
+        let (pupil_radius, normalization) = self.exit_pupil(film_distance, film_radius);
+        LensFocus {
+            film_distance,
+            pupil_radius,
+            normalization,
+        }
+    }
+
+    /// A ray from the film point at (`x`, `y`) out through the lens, `None` when
+    /// the lens blocks it.
+    pub(crate) fn sample_ray(&self, focus: &LensFocus, x: f64, y: f64) -> Option<LensRay> {
+        let (radius, angle): (f64, f64) = random::with_rng(|rng| (rng.gen(), rng.gen()));
+        let (radius, angle) = (focus.pupil_radius * radius.sqrt(), 2.0 * PI * angle);
+        let origin = Vec3::new(x, y, 0.0);
+        let on_pupil = Vec3::new(
+            radius * angle.cos(),
+            radius * angle.sin(),
+            focus.film_distance,
+        );
+        let direction = (on_pupil - origin).unit_vector();
+        let (origin, outgoing) = self.trace_from_film(focus.film_distance, origin, direction)?;
+        Some(LensRay {
+            origin,
+            direction: outgoing,
+            // the film gets less light at an angle, the cosine fourth law
+            weight: direction.z().powi(4) * focus.normalization,
+        })
+    }
+
+    /// Front focal point, front principal plane, rear focal point and rear
+    /// principal plane on the axis, with the film at the last surface.
+    fn cardinal_points(&self) -> (f64, f64, f64, f64) {
+        let height = 0.01 * self.surfaces[0].aperture_radius;
+        let front = self.surfaces[0].z + 1.0;
+        let from_scene =
+            self.trace_from_scene(Vec3::new(height, 0.0, front), Vec3::new(0.0, 0.0, -1.0));
+        let from_film =
+            self.trace_from_film(0.0, Vec3::new(height, 0.0, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let points = |traced: Option<(Vec3, Vec3)>| match traced {
+            Some((origin, direction)) => {
+                let at_height =
+                    |x: f64| origin.z() + (x - origin.x()) / direction.x() * direction.z();
+                (at_height(0.0), at_height(height))
+            }
+            None => (f64::NAN, f64::NAN),
+        };
+        let (front_focal_point, front_principal_plane) = points(from_film);
+        let (rear_focal_point, rear_principal_plane) = points(from_scene);
+        (
+            front_focal_point,
+            front_principal_plane,
+            rear_focal_point,
+            rear_principal_plane,
+        )
+    }
+
+    /// How far from the center of the back surface light gets through from some
+    /// point of the film, and the share of that disk that is open at the center.
+    fn exit_pupil(&self, film_distance: f64, film_radius: f64) -> (f64, f64) {
+        const STEPS: usize = 32;
+        let rear = self.surfaces[self.surfaces.len() - 1].aperture_radius;
+        let spacing = 2.0 * rear / STEPS as f64;
+        let grid = || {
+            (0..STEPS * STEPS).map(move |i| {
+                let x = -rear + spacing * ((i % STEPS) as f64 + 0.5);
+                let y = -rear + spacing * ((i / STEPS) as f64 + 0.5);
+                (x, y)
+            })
+        };
+        let passes = |film_x: f64, (x, y): (f64, f64)| {
+            let origin = Vec3::new(film_x, 0.0, 0.0);
+            let direction = (Vec3::new(x, y, film_distance) - origin).unit_vector();
+            x * x + y * y <= rear * rear
+                && self
+                    .trace_from_film(film_distance, origin, direction)
+                    .is_some()
+        };
+        let pupil_radius = (0..=8)
+            .map(|step| film_radius * step as f64 / 8.0)
+            .flat_map(|film_x| grid().filter(move |p| passes(film_x, *p)))
+            .map(|(x, y)| (x * x + y * y).sqrt() + spacing)
+            .fold(0.0, f64::max)
+            .min(rear);
+        let in_pupil: Vec<_> = grid()
+            .filter(|(x, y)| x * x + y * y <= pupil_radius * pupil_radius)
+            .collect();
+        let open = in_pupil.iter().filter(|p| passes(0.0, **p)).count();
+        let normalization = match open {
+            0 => 0.0,
+            open => in_pupil.len() as f64 / open as f64,
+        };
+        (pupil_radius, normalization)
+    }
+
+    fn trace_from_film(
+        &self,
+        film_distance: f64,
+        mut origin: Vec3,
+        mut direction: Vec3,
+    ) -> Option<(Vec3, Vec3)> {
+        for surface in self.surfaces.iter().rev() {
+            let ior = surface.ior_back / surface.ior_front;
+            (origin, direction) =
+                surface.refract(surface.z + film_distance, origin, direction, ior)?;
+        }
+        Some((origin, direction))
+    }
+
+    fn trace_from_scene(&self, mut origin: Vec3, mut direction: Vec3) -> Option<(Vec3, Vec3)> {
+        for surface in self.surfaces.iter() {
+            let ior = surface.ior_front / surface.ior_back;
+            (origin, direction) = surface.refract(surface.z, origin, direction, ior)?;
+        }
+        Some((origin, direction))
+    }
+}
+
+impl PartialEq for Lens {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path && self.aperture == other.aperture
+    }
+}
+
+impl TryFrom<LensSettings> for Lens {
+    type Error = String;
+
+    fn try_from(value: LensSettings) -> Result<Self, Self::Error> {
+        Self::load(crate::scene::scene_file(&value.path)?, value.aperture)
+    }
+}
+
+impl From<Lens> for LensSettings {
+    fn from(value: Lens) -> Self {
+        Self {
+            path: value.path,
+            aperture: value.aperture,
+        }
+    }
+}
+
+impl Surface {
+    /// Where the ray hits this surface with its vertex at `z` and the direction
+    /// it leaves in, `ior` being the ratio of the indices it comes from and goes to.
+    fn refract(&self, z: f64, origin: Vec3, direction: Vec3, ior: f64) -> Option<(Vec3, Vec3)> {
+        let (t, normal) = match self.radius == 0.0 {
+            true => ((z - origin.z()) / direction.z(), Vec3::new(0.0, 0.0, 1.0)),
+            false => {
+                let center = Vec3::new(0.0, 0.0, z - self.radius);
+                let oc = origin - center;
+                let b = oc.dot(&direction);
+                let c = oc.length_squared() - self.radius * self.radius;
+                let discriminant = b * b - c;
+                if discriminant < 0.0 {
+                    return None;
+                }
+                // the half of the sphere the vertex is on
+                let closer = (*direction.z() < 0.0) ^ (self.radius < 0.0);
+                let t = match closer {
+                    true => -b - discriminant.sqrt(),
+                    false => -b + discriminant.sqrt(),
+                };
+                (t, (origin + t * direction - center) / self.radius.abs())
+            }
+        };
+        if t.is_nan() || t <= 0.0 {
+            return None;
+        }
+        let hit = origin + t * direction;
+        if hit.x() * hit.x() + hit.y() * hit.y() > self.aperture_radius * self.aperture_radius {
+            return None;
+        }
+        if self.radius == 0.0 {
+            return Some((hit, direction));
+        }
+        let normal = match normal.dot(&direction) > 0.0 {
+            true => -normal,
+            false => normal,
+        };
+        let cos_in = -normal.dot(&direction);
+        let sin2_out = ior * ior * (1.0 - cos_in * cos_in);
+        if sin2_out > 1.0 {
+            return None;
+        }
+        let refracted = ior * direction + (ior * cos_in - (1.0 - sin2_out).sqrt()) * normal;
+        Some((hit, refracted.unit_vector()))
+    }
+}
+
+/// The surfaces of a prescription, the line of an error comes first in it.
+fn parse(source: &str, aperture: Option<f64>) -> Result<Vec<Surface>, String> {
+    let mut rows = Vec::new();
+    for (number, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let values = line
+            .split_whitespace()
+            .map(str::parse::<f64>)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("{}: {}", number + 1, e))?;
+        let [radius, thickness, ior, diameter] = values[..] else {
+            return Err(format!(
+                "{}: expected radius, thickness, index of refraction and diameter",
+                number + 1
+            ));
+        };
+        rows.push((radius, thickness, ior, diameter));
+    }
+    if rows.is_empty() {
+        return Err("1: the lens has no surfaces".to_string());
+    }
+    let mut z = 0.0;
+    let mut surfaces = Vec::new();
+    // back to front, so the last surface ends up at 0
+    for (i, (radius, thickness, ior, diameter)) in rows.iter().enumerate().rev() {
+        if i + 1 < rows.len() {
+            z += thickness;
+        }
+        let diameter = match (radius, aperture) {
+            (radius, Some(aperture)) if *radius == 0.0 => aperture,
+            _ => *diameter,
+        };
+        surfaces.push(Surface {
+            z,
+            radius: *radius,
+            aperture_radius: diameter / 2.0,
+            ior_front: 1.0,
+            ior_back: if *ior == 0.0 { 1.0 } else { *ior },
+        });
+    }
+    surfaces.reverse();
+    for i in 1..surfaces.len() {
+        surfaces[i].ior_front = surfaces[i - 1].ior_back;
+    }
+    Ok(surfaces)
+}
@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+/// Camera settings as on a real camera, with world units taken to be meters and
+/// scene time to be seconds.
+///
+/// The focal length and sensor give the field of view, the aperture the depth of
+/// field, the shutter how long moving things are seen for and all of them plus
+/// the ISO how bright the image gets. Exposure follows the sunny 16 rule: at f/16
+/// with a shutter of one over the ISO a radiance of 1 comes out as 1.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PhysicalCamera {
+    /// In millimeters.
+    focal_length: f64,
+    /// Width and height in millimeters, the image uses as much of it as it can.
+    sensor: [f64; 2],
+    f_number: f64,
+    /// Shutter duration in seconds, rays are sent at times from 0 up to it.
+    shutter: f64,
+    iso: f64,
+}
+
+impl Default for PhysicalCamera {
+    /// A 50mm lens on a full frame sensor, set up for a sunny day.
+    fn default() -> Self {
+        Self {
+            focal_length: 50.0,
+            sensor: [36.0, 24.0],
+            f_number: 16.0,
+            shutter: 0.01,
+            iso: 100.0,
+        }
+    }
+}
+
+impl PhysicalCamera {
+    pub const fn set_focal_length(mut self, focal_length: f64) -> Self {
+        self.focal_length = focal_length;
+        self
+    }
+
+    pub const fn set_sensor(mut self, width: f64, height: f64) -> Self {
+        self.sensor = [width, height];
+        self
+    }
+
+    pub const fn set_f_number(mut self, f_number: f64) -> Self {
+        self.f_number = f_number;
+        self
+    }
+
+    pub const fn set_shutter(mut self, shutter: f64) -> Self {
+        self.shutter = shutter;
+        self
+    }
+
+    pub const fn set_iso(mut self, iso: f64) -> Self {
+        self.iso = iso;
+        self
+    }
+
+    pub const fn focal_length(&self) -> f64 {
+        self.focal_length
+    }
+
+    pub const fn sensor(&self) -> [f64; 2] {
+        self.sensor
+    }
+
+    pub const fn f_number(&self) -> f64 {
+        self.f_number
+    }
+
+    pub const fn shutter(&self) -> f64 {
+        self.shutter
+    }
+
+    pub const fn iso(&self) -> f64 {
+        self.iso
+    }
+
+    /// Vertical field of view in degrees of an image `aspect_ratio` times as wide
+    /// as it is high. Images wider than the sensor are cropped at the top and
+    /// bottom, narrower ones at the sides.
+    pub fn vfov(&self, aspect_ratio: f64) -> f64 {
+        let [width, height] = self.sensor;
+        let used_height = height.min(width / aspect_ratio);
+        2.0 * (used_height / (2.0 * self.focal_length))
+            .atan()
+            .to_degrees()
+    }
+
+    /// Radius of the aperture in meters.
+    pub fn aperture_radius(&self) -> f64 {
+        self.focal_length / self.f_number / 2.0 / 1000.0
+    }
+
+    /// The angle the aperture covers seen from `focus_dist`, in degrees, which is
+    /// how [`super::CameraInfo`] describes the depth of field.
+    pub fn defocus_angle(&self, focus_dist: f64) -> f64 {
+        2.0 * (self.aperture_radius() / focus_dist).atan().to_degrees()
+    }
+
+    /// What the gathered radiance gets multiplied by.
+    pub fn exposure(&self) -> f64 {
+        // at f/16 and 1/iso seconds this is 1
+        256.0 * self.shutter * self.iso / (self.f_number * self.f_number)
+    }
+}
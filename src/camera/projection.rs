@@ -0,0 +1,223 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::vector::Vec3;
+
+/// How the camera maps directions onto the image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Projection {
+    /// A pinhole or thin lens camera with the vertical field of view `vfov`.
+    #[default]
+    Perspective,
+    /// Parallel rays, `view_height` world units of the scene fit the image height.
+    /// Depth of field still works, the lens then sits in front of every pixel.
+    Orthographic { view_height: f64 },
+    /// Every direction around the camera, longitude across and latitude down the
+    /// image. The image should be twice as wide as it is high.
+    Equirectangular,
+    /// A circular fisheye image seeing `fov` degrees across, which may be more than
+    /// 180. The circle fills the shorter side of the image, the rest stays black.
+    Fisheye {
+        fov: f64,
+        #[serde(default)]
+        mapping: FisheyeMapping,
+    },
+    /// The six 90 degree views of a cube around the camera.
+    Cubemap {
+        #[serde(default)]
+        layout: CubemapLayout,
+    },
+}
+
+/// How the angle from the view direction becomes a distance from the image center.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FisheyeMapping {
+    /// Distance grows linearly with the angle.
+    #[default]
+    Equidistant,
+    /// Equal solid angles cover equal areas of the image.
+    Equisolid,
+}
+
+/// How the faces of a cubemap are arranged.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CubemapLayout {
+    /// A horizontal cross in a 4:3 image, left, front, right and back in the
+    /// middle row with up and down above and below the front.
+    #[default]
+    Cross,
+    /// The faces next to each other in a 6:1 image, in the order of
+    /// [`CubeFace::ALL`]. Written to a file each when the output is a file.
+    Separate,
+}
+
+/// One side of a cubemap, named from the point of view of the camera.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CubeFace {
+    Front,
+    Right,
+    Back,
+    Left,
+    Up,
+    Down,
+}
+
+/// The camera's orientation, `w` points backwards like in [`super::CameraInfo`].
+#[derive(Clone, Copy)]
+pub(crate) struct Basis {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Projection {
+    pub const fn is_perspective(&self) -> bool {
+        matches!(self, Projection::Perspective)
+    }
+
+    /// Whether rays leave from a single point in directions all around it, rather
+    /// than through a viewport in front of the camera.
+    pub const fn is_panoramic(&self) -> bool {
+        !matches!(
+            self,
+            Projection::Perspective | Projection::Orthographic { .. }
+        )
+    }
+
+    /// The width to height ratio images of this projection are made for, if any.
+    pub const fn aspect_ratio(&self) -> Option<f64> {
+        match self {
+            Projection::Perspective | Projection::Orthographic { .. } => None,
+            Projection::Equirectangular => Some(2.0),
+            Projection::Fisheye { .. } => Some(1.0),
+            Projection::Cubemap {
+                layout: CubemapLayout::Cross,
+            } => Some(4.0 / 3.0),
+            Projection::Cubemap {
+                layout: CubemapLayout::Separate,
+            } => Some(6.0),
+        }
+    }
+
+    /// The direction seen at (`x`, `y`) of a `width` by `height` image, in pixels
+    /// from the top left corner. `None` where the image shows nothing.
+    /// Only used for panoramic projections.
+    pub(crate) fn direction(
+        &self,
+        basis: &Basis,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    ) -> Option<Vec3> {
+        match *self {
+            Projection::Perspective | Projection::Orthographic { .. } => None,
+            Projection::Equirectangular => {
+                let longitude = (x / width - 0.5) * 2.0 * PI;
+                let latitude = (0.5 - y / height) * PI;
+                Some(
+                    latitude.cos() * longitude.sin() * basis.u + latitude.sin() * basis.v
+                        - latitude.cos() * longitude.cos() * basis.w,
+                )
+            }
+            Projection::Fisheye { fov, mapping } => {
+                let radius = width.min(height) / 2.0;
+                let dx = (x - width / 2.0) / radius;
+                let dy = (height / 2.0 - y) / radius;
+                let r = (dx * dx + dy * dy).sqrt();
+                if r > 1.0 {
+                    return None;
+                }
+                let max_angle = fov.to_radians() / 2.0;
+                let angle = match mapping {
+                    FisheyeMapping::Equidistant => r * max_angle,
+                    FisheyeMapping::Equisolid => {
+                        2.0 * (r * (max_angle / 2.0).sin()).clamp(-1.0, 1.0).asin()
+                    }
+                };
+                let (dx, dy) = match r > 0.0 {
+                    true => (dx / r, dy / r),
+                    false => (0.0, 0.0),
+                };
+                Some(angle.sin() * (dx * basis.u + dy * basis.v) - angle.cos() * basis.w)
+            }
+            Projection::Cubemap { layout } => {
+                let (columns, rows) = match layout {
+                    CubemapLayout::Cross => (4.0, 3.0),
+                    CubemapLayout::Separate => (6.0, 1.0),
+                };
+                let size = (width / columns).min(height / rows);
+                let (column, row) = ((x / size).floor(), (y / size).floor());
+                if column >= columns || row >= rows {
+                    return None;
+                }
+                let face = layout.face_at(column as usize, row as usize)?;
+                let s = 2.0 * (x / size - column) - 1.0;
+                let t = 2.0 * (y / size - row) - 1.0;
+                let (forward, right, up) = face.axes(basis);
+                Some(forward + s * right - t * up)
+            }
+        }
+    }
+}
+
+impl CubemapLayout {
+    /// The face at column `column` and row `row` of the layout, counted in faces.
+    pub const fn face_at(&self, column: usize, row: usize) -> Option<CubeFace> {
+        match self {
+            CubemapLayout::Cross => match (column, row) {
+                (1, 0) => Some(CubeFace::Up),
+                (0, 1) => Some(CubeFace::Left),
+                (1, 1) => Some(CubeFace::Front),
+                (2, 1) => Some(CubeFace::Right),
+                (3, 1) => Some(CubeFace::Back),
+                (1, 2) => Some(CubeFace::Down),
+                _ => None,
+            },
+            CubemapLayout::Separate => match row {
+                0 if column < CubeFace::ALL.len() => Some(CubeFace::ALL[column]),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::Front,
+        CubeFace::Right,
+        CubeFace::Back,
+        CubeFace::Left,
+        CubeFace::Up,
+        CubeFace::Down,
+    ];
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            CubeFace::Front => "front",
+            CubeFace::Right => "right",
+            CubeFace::Back => "back",
+            CubeFace::Left => "left",
+            CubeFace::Up => "up",
+            CubeFace::Down => "down",
+        }
+    }
+
+    /// The direction through the center of the face and the directions that are
+    /// right and up in its image. Neighbouring faces of the cross line up.
+    fn axes(&self, basis: &Basis) -> (Vec3, Vec3, Vec3) {
+        let Basis { u, v, w } = *basis;
+        match self {
+            CubeFace::Front => (-w, u, v),
+            CubeFace::Right => (u, w, v),
+            CubeFace::Back => (w, -u, v),
+            CubeFace::Left => (-u, -w, v),
+            CubeFace::Up => (v, u, w),
+            CubeFace::Down => (-v, u, -w),
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+
+/// Two eyes next to each other instead of one camera, both rendered into one image.
+///
+/// Perspective eyes look in parallel with their viewports shifted so they line up
+/// at the convergence distance. Panoramic projections become omni-directional
+/// stereo, where the eyes sit on a circle and every direction is seen from the
+/// point of that circle where it is tangent.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Stereo {
+    /// Distance between the eyes in world units.
+    interocular: f64,
+    /// Distance at which the eyes see things at the same spot, parallel when missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    convergence: Option<f64>,
+    #[serde(default)]
+    layout: StereoLayout,
+}
+
+/// Where the eyes go in the image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StereoLayout {
+    /// Left eye in the left half, right eye in the right half.
+    #[default]
+    SideBySide,
+    /// Left eye in the top half, right eye in the bottom half.
+    OverUnder,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+impl Stereo {
+    pub const fn new(interocular: f64) -> Self {
+        Self {
+            interocular,
+            convergence: None,
+            layout: StereoLayout::SideBySide,
+        }
+    }
+
+    pub const fn set_convergence(mut self, convergence: Option<f64>) -> Self {
+        self.convergence = convergence;
+        self
+    }
+
+    pub const fn set_layout(mut self, layout: StereoLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    pub const fn interocular(&self) -> f64 {
+        self.interocular
+    }
+
+    pub const fn convergence(&self) -> Option<f64> {
+        self.convergence
+    }
+
+    pub const fn layout(&self) -> StereoLayout {
+        self.layout
+    }
+
+    /// How far along the camera's right the eye sits, negative for the left one.
+    pub const fn eye_offset(&self, eye: Eye) -> f64 {
+        match eye {
+            Eye::Left => -self.interocular / 2.0,
+            Eye::Right => self.interocular / 2.0,
+        }
+    }
+
+    /// The size of one eye's image in a `width` by `height` image.
+    pub const fn eye_size(&self, width: usize, height: usize) -> (usize, usize) {
+        match self.layout {
+            StereoLayout::SideBySide => (width / 2, height),
+            StereoLayout::OverUnder => (width, height / 2),
+        }
+    }
+
+    /// The eye that pixel (`x`, `y`) belongs to and where it is in that eye's image.
+    /// `None` for the odd row or column left over when the image does not split evenly.
+    pub const fn eye_pixel(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Option<(Eye, usize, usize)> {
+        let (eye_width, eye_height) = self.eye_size(width, height);
+        let (eye, x, y) = match self.layout {
+            StereoLayout::SideBySide if x < eye_width => (Eye::Left, x, y),
+            StereoLayout::SideBySide => (Eye::Right, x - eye_width, y),
+            StereoLayout::OverUnder if y < eye_height => (Eye::Left, x, y),
+            StereoLayout::OverUnder => (Eye::Right, x, y - eye_height),
+        };
+        match x < eye_width && y < eye_height {
+            true => Some((eye, x, y)),
+            false => None,
+        }
+    }
+
+    /// Turns the width to height ratio of one eye into that of the whole image.
+    pub fn image_aspect_ratio(&self, eye_aspect_ratio: f64) -> f64 {
+        match self.layout {
+            StereoLayout::SideBySide => eye_aspect_ratio * 2.0,
+            StereoLayout::OverUnder => eye_aspect_ratio / 2.0,
+        }
+    }
+}
@@ -0,0 +1,422 @@
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    process::{Child, Command as Process},
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use clap::{Parser, Subcommand};
+
+use raytracer::{
+    camera::Camera,
+    denoise::Denoiser,
+    distributed::{self, Coordinator},
+    film::Film,
+    gallery,
+    output::{ImageFormat, Output},
+    progress::{CancellationToken, ProgressBarObserver, ProgressObserver, Silent},
+    render_state::RenderState,
+    scene::Scene,
+    server::Server,
+    tiles::TileOrder,
+    tonemap::{PostProcess, ToneMapper},
+};
+
+/// Renders a scene file or one of the built in scenes to an image.
+#[derive(Parser, Debug)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// TOML scene file to render
+    scene: Option<PathBuf>,
+    /// Built in scene to render instead of a scene file, see --list-scenes
+    #[arg(
+        short,
+        long,
+        conflicts_with = "scene",
+        default_value = "random-spheres"
+    )]
+    builtin: String,
+    /// Print the built in scenes and exit
+    #[arg(long)]
+    list_scenes: bool,
+    /// Image width in pixels
+    #[arg(short = 'W', long)]
+    width: Option<usize>,
+    /// Image height in pixels
+    #[arg(short = 'H', long)]
+    height: Option<usize>,
+    /// Aspect ratio used when only one of width and height is given, as 1.5 or 16:9
+    #[arg(short, long, value_parser = parse_aspect_ratio)]
+    aspect: Option<f64>,
+    /// Samples per pixel
+    #[arg(short, long)]
+    spp: Option<usize>,
+    /// Maximum number of bounces per ray
+    #[arg(short = 'd', long)]
+    max_depth: Option<usize>,
+    /// Seed for reproducible renders
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Width and height of the render tiles in pixels
+    #[arg(long, default_value_t = 32)]
+    tile_size: usize,
+    /// Order tiles are rendered in: scanline, spiral or hilbert
+    #[arg(long, default_value = "scanline")]
+    tile_order: TileOrder,
+    /// Save the render progress to this file now and then, and when done
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+    /// Seconds between checkpoints
+    #[arg(long, default_value_t = 60.0)]
+    checkpoint_interval: f64,
+    /// Continue the render saved in the checkpoint file up to the samples per pixel
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
+    /// Render passes of this many spp and write the image after each one
+    #[arg(long, value_name = "SPP")]
+    progressive: Option<usize>,
+    /// Stop rendering after this many seconds and keep what is done
+    #[arg(long, value_name = "SECONDS")]
+    time_limit: Option<f64>,
+    /// Hand the tiles out to worker processes connecting to this address
+    #[arg(long, value_name = "ADDRESS", conflicts_with = "progressive")]
+    listen: Option<String>,
+    /// Start this many worker processes on this machine, implies --listen
+    #[arg(long, value_name = "N", conflicts_with = "progressive")]
+    local_workers: Option<usize>,
+    /// Number of render threads, all cores by default
+    #[arg(short = 'j', long)]
+    threads: Option<usize>,
+    /// Image to write, `-` for stdout
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// ppm, p6, png8, png16, exr[:options], hdr or pfm, guessed from the output extension by default
+    #[arg(short, long)]
+    format: Option<ImageFormat>,
+    /// Exposure compensation in stops
+    #[arg(short, long, default_value_t = 0.0, allow_hyphen_values = true)]
+    exposure: f64,
+    /// Color temperature in kelvin to balance to
+    #[arg(long)]
+    white_balance: Option<f64>,
+    /// clamp, reinhard[=white], hable, aces or agx
+    #[arg(short, long, default_value = "clamp")]
+    tonemap: ToneMapper,
+    /// Denoise the image, optionally with a strength other than 1
+    #[arg(long, num_args = 0..=1, default_missing_value = "1.0")]
+    denoise: Option<f64>,
+    /// Only print errors
+    #[arg(short, long)]
+    quiet: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Render tiles for a coordinator started with --listen
+    Worker {
+        /// Address the coordinator listens on
+        coordinator: String,
+        /// Number of render threads, all cores by default
+        #[arg(short = 'j', long)]
+        threads: Option<usize>,
+        /// Only print errors
+        #[arg(short, long)]
+        quiet: bool,
+    },
+    /// Accept render jobs over HTTP, see the `server` module for the API
+    Serve {
+        /// Address to listen on
+        #[arg(default_value = "127.0.0.1:8080")]
+        address: String,
+    },
+}
+
+fn parse_aspect_ratio(s: &str) -> Result<f64, String> {
+    let ratio = match s.split_once(':') {
+        Some((w, h)) => {
+            let w: f64 = w
+                .trim()
+                .parse()
+                .map_err(|_| format!("bad width in '{}'", s))?;
+            let h: f64 = h
+                .trim()
+                .parse()
+                .map_err(|_| format!("bad height in '{}'", s))?;
+            w / h
+        }
+        None => s
+            .parse()
+            .map_err(|_| format!("'{}' is not an aspect ratio", s))?,
+    };
+    match ratio.is_finite() && ratio > 0.0 {
+        true => Ok(ratio),
+        false => Err(format!("'{}' is not a positive aspect ratio", s)),
+    }
+}
+
+impl Cli {
+    pub fn run(self) -> Result<(), Box<dyn Error>> {
+        match &self.command {
+            Some(Command::Worker {
+                coordinator,
+                threads,
+                quiet,
+            }) => {
+                if let Some(threads) = threads {
+                    rayon::ThreadPoolBuilder::new()
+                        .num_threads(*threads)
+                        .build_global()?;
+                }
+                let tiles = distributed::work(coordinator.as_str())
+                    .map_err(|e| format!("worker for {}: {}", coordinator, e))?;
+                if !quiet {
+                    eprintln!("worker for {} rendered {} tiles", coordinator, tiles);
+                }
+                return Ok(());
+            }
+            Some(Command::Serve { address }) => {
+                let server = Server::bind(address.as_str())
+                    .map_err(|e| format!("cannot listen on {}: {}", address, e))?;
+                eprintln!("serving render jobs on http://{}", server.local_addr()?);
+                server.run()?;
+                return Ok(());
+            }
+            None => {}
+        }
+        if self.list_scenes {
+            for scene in gallery::SCENES {
+                println!("{:<18} {}", scene.name, scene.description);
+            }
+            return Ok(());
+        }
+        if let Some(threads) = self.threads {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build_global()?;
+        }
+        let output = self.output()?;
+        let mut scene = match &self.scene {
+            Some(path) => Scene::load(path)?,
+            None => gallery::build(&self.builtin, self.seed)
+                .ok_or_else(|| format!("there is no built in scene named '{}'", self.builtin))?,
+        };
+        self.log("Setup done");
+        scene.camera = self
+            .configure(scene.camera)
+            .set_aovs(output.format().supports_aovs() || self.denoise.is_some());
+        if self.progressive.is_some() && output.path().is_none() {
+            return Err("progressive rendering needs an output file".into());
+        }
+        let (film, written) = self.render(&scene, &output)?;
+        // progressive passes write the image as they go, unless none were needed
+        if !written {
+            self.write(film, &scene.camera, &output)?;
+        }
+        Ok(())
+    }
+
+    /// Renders, resuming from and saving to the checkpoint file when there is one.
+    /// Also tells whether a progressive pass has written the image already.
+    fn render(&self, scene: &Scene, output: &Output) -> Result<(Film, bool), Box<dyn Error>> {
+        let (camera, world) = (&scene.camera, &scene.world);
+        let coordinator = match (&self.listen, self.local_workers) {
+            (Some(address), _) => Some(Coordinator::bind(address.as_str())?),
+            (None, Some(_)) => Some(Coordinator::bind("127.0.0.1:0")?),
+            (None, None) => None,
+        }
+        .map(|coordinator| coordinator.set_verbose(!self.quiet));
+        let mut workers = Vec::new();
+        if let Some(coordinator) = &coordinator {
+            let address = coordinator.local_addr()?;
+            self.log(&format!("waiting for workers on {}", address));
+            let count = self.local_workers.unwrap_or(0);
+            // the workers share the cores instead of each using all of them
+            let threads = (rayon::current_num_threads() / count.max(1)).max(1);
+            for _ in 0..count {
+                workers.push(spawn_worker(&address.to_string(), threads)?);
+            }
+        }
+        let state = match (&self.checkpoint, self.resume) {
+            (Some(path), true) => self.resume(scene, path)?,
+            _ => camera.render_state().set_scene(scene.fingerprint()),
+        };
+        let mut cancel = CancellationToken::new();
+        if let Some(seconds) = self.time_limit {
+            cancel =
+                cancel.with_deadline(Instant::now() + Duration::from_secs_f64(seconds.max(0.0)));
+        }
+        let observer: &dyn ProgressObserver = match self.quiet {
+            true => &Silent,
+            false => &ProgressBarObserver::new(),
+        };
+        let (done, saver_stop) = mpsc::channel::<()>();
+        let saved = &state;
+        let interval = Duration::from_secs_f64(self.checkpoint_interval.max(0.0));
+        let mut written = false;
+        let rendered = std::thread::scope(|scope| {
+            if let Some(path) = &self.checkpoint {
+                // saves every interval until the render is done
+                scope.spawn(move || {
+                    while let Err(mpsc::RecvTimeoutError::Timeout) =
+                        saver_stop.recv_timeout(interval)
+                    {
+                        if let Err(e) = saved.save(path) {
+                            eprintln!("could not write checkpoint {}: {}", path.display(), e);
+                        }
+                    }
+                });
+            }
+            let rendered = match (&coordinator, self.progressive) {
+                (Some(coordinator), _) => coordinator
+                    .render(scene, &state, observer, &cancel)
+                    .map_err(Into::into),
+                (None, Some(pass)) => {
+                    camera.render_progressive(world, &state, pass, observer, &cancel, |state| {
+                        self.log(&format!("pass done at {} spp", state.samples_per_pixel()));
+                        written = true;
+                        self.write(state.film(), camera, output)
+                    })
+                }
+                (None, None) => {
+                    camera.render_into(world, &state, observer, &cancel);
+                    Ok(())
+                }
+            };
+            drop(done);
+            rendered
+        });
+        for mut worker in workers {
+            if rendered.is_err() {
+                let _ = worker.kill();
+            }
+            worker.wait()?;
+        }
+        rendered?;
+        if let Some(path) = &self.checkpoint {
+            state
+                .save(path)
+                .map_err(|e| format!("could not write checkpoint {}: {}", path.display(), e))?;
+        }
+        Ok((state.film(), written))
+    }
+
+    fn resume(&self, scene: &Scene, path: &Path) -> Result<RenderState, String> {
+        let camera = &scene.camera;
+        let state = RenderState::load(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if (state.width(), state.height()) != (camera.image_width(), camera.image_height()) {
+            return Err(format!(
+                "{} holds a {}x{} render but the image is {}x{}",
+                path.display(),
+                state.width(),
+                state.height(),
+                camera.image_width(),
+                camera.image_height()
+            ));
+        }
+        if state.scene() != scene.fingerprint() {
+            return Err(format!(
+                "{} holds a render of another scene or camera",
+                path.display()
+            ));
+        }
+        if state.has_aovs() != camera.aovs() {
+            let with = |aovs: bool| match aovs {
+                true => "with",
+                false => "without",
+            };
+            return Err(format!(
+                "{} holds a render {} aovs but this one is {} them",
+                path.display(),
+                with(state.has_aovs()),
+                with(camera.aovs())
+            ));
+        }
+        self.log(&format!("resuming from {} spp", state.samples_per_pixel()));
+        Ok(state)
+    }
+
+    fn write(
+        &self,
+        mut film: Film,
+        camera: &Camera,
+        output: &Output,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(strength) = self.denoise {
+            self.log("denoising");
+            film = Denoiser::default().set_strength(strength).denoise(&film);
+        }
+        if let (Some(faces), Some(_)) = (camera.cubemap_faces(&film), output.path()) {
+            self.log(&format!("writing {} cubemap faces", output.format()));
+            for (face, film) in faces {
+                output.with_suffix(face.name()).write(&film)?;
+            }
+            return Ok(());
+        }
+        self.log(&format!("writing {} image", output.format()));
+        output.write(&film)?;
+        Ok(())
+    }
+
+    fn log(&self, message: &str) {
+        if !self.quiet {
+            eprintln!("{}", message);
+        }
+    }
+
+    fn output(&self) -> Result<Output, String> {
+        let output = match (self.output.as_str(), self.format) {
+            ("-", format) => Output::stdout(format.unwrap_or(ImageFormat::Ppm)),
+            (path, Some(format)) => Output::new(path, format),
+            (path, None) => Output::file(path)?,
+        };
+        let mut post_process = PostProcess::default()
+            .set_exposure(self.exposure)
+            .set_tone_mapper(self.tonemap);
+        if let Some(temperature) = self.white_balance {
+            post_process = post_process.set_white_balance(temperature);
+        }
+        Ok(output.set_post_process(post_process))
+    }
+
+    /// Lets the command line override what the scene asks for.
+    fn configure(&self, mut camera: Camera) -> Camera {
+        let aspect = self
+            .aspect
+            .unwrap_or(camera.image_width() as f64 / camera.image_height() as f64);
+        camera = match (self.width, self.height) {
+            (Some(width), Some(height)) => camera
+                .set_image_width_with_aspect_ratio(width, width as f64 / height as f64)
+                .set_image_height(height),
+            (Some(width), None) => camera.set_image_width_with_aspect_ratio(width, aspect),
+            (None, Some(height)) => camera.set_image_height_with_aspect_ratio(height, aspect),
+            (None, None) if self.aspect.is_some() => {
+                let width = camera.image_width();
+                camera.set_image_width_with_aspect_ratio(width, aspect)
+            }
+            (None, None) => camera,
+        };
+        if let Some(spp) = self.spp {
+            camera = camera.set_samples_per_pixel(spp);
+        }
+        if let Some(max_depth) = self.max_depth {
+            camera = camera.set_max_depth(max_depth);
+        }
+        camera
+            .set_seed(self.seed)
+            .set_verbose(!self.quiet)
+            .set_tile_size(self.tile_size)
+            .set_tile_order(self.tile_order)
+    }
+}
+
+/// Starts this program again as a worker for the coordinator at `address`, rendering
+/// on `threads` threads.
+fn spawn_worker(address: &str, threads: usize) -> Result<Child, Box<dyn Error>> {
+    let program = std::env::current_exe()?;
+    Ok(Process::new(program)
+        .args(["worker", "--quiet", "-j", &threads.to_string(), address])
+        .spawn()?)
+}
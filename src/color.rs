@@ -1,30 +1,35 @@
+//! Colors with channels in the 0-1 range and their sRGB encoding.
+
 use std::{
     fmt::Display,
     iter::Sum,
     ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub},
 };
 
+use serde::{Deserialize, Serialize};
+
 use crate::vector::Vec3;
 
-#[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
+/// Linear RGB with every channel in 0-1, for reflectances and textures.
+///
+/// Serialized like a [`Vec3`], deserializing fails when a channel is outside 0-1.
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
+#[serde(try_from = "Vec3", into = "Vec3")]
 pub struct Color(Vec3);
 
 impl Display for Color {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let min = 0.0;
-        let max = 0.99999;
-
-        let r = self.red().sqrt();
-        let g = self.green().sqrt();
-        let b = self.blue().sqrt();
-
-        write!(
-            f,
-            "{} {} {}",
-            (256. * r.clamp(min, max)) as u8,
-            (256. * g.clamp(min, max)) as u8,
-            (256. * b.clamp(min, max)) as u8
-        )
+        let [r, g, b] = self.to_srgb8();
+        write!(f, "{} {} {}", r, g, b)
+    }
+}
+/// The sRGB transfer function, turning linear light into display encoded values.
+pub fn linear_to_srgb(linear: f64) -> f64 {
+    let linear = linear.clamp(0.0, 1.0);
+    if linear <= 0.0031308 {
+        12.92 * linear
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
     }
 }
 impl Color {
@@ -67,13 +72,13 @@ impl Color {
     pub const fn blue(&self) -> &f64 {
         self.0.z()
     }
-    pub fn red_mut(&mut self) -> &mut f64 {
+    pub const fn red_mut(&mut self) -> &mut f64 {
         self.0.x_mut()
     }
-    pub fn green_mut(&mut self) -> &mut f64 {
+    pub const fn green_mut(&mut self) -> &mut f64 {
         self.0.y_mut()
     }
-    pub fn blue_mut(&mut self) -> &mut f64 {
+    pub const fn blue_mut(&mut self) -> &mut f64 {
         self.0.z_mut()
     }
     pub fn validate_or_crash(&self) {
@@ -93,6 +98,23 @@ impl Color {
             self.blue()
         );
     }
+    /// Clamps every channel into 0-1 instead of crashing like [`Color::new`].
+    pub const fn clamped(v: Vec3) -> Self {
+        Self(Vec3::new(
+            v.x.clamp(0.0, 1.0),
+            v.y.clamp(0.0, 1.0),
+            v.z.clamp(0.0, 1.0),
+        ))
+    }
+    pub fn to_srgb8(&self) -> [u8; 3] {
+        self.encode_srgb(u8::MAX as f64).map(|c| c as u8)
+    }
+    pub fn to_srgb16(&self) -> [u16; 3] {
+        self.encode_srgb(u16::MAX as f64).map(|c| c as u16)
+    }
+    fn encode_srgb(&self, max: f64) -> [f64; 3] {
+        [self.red(), self.green(), self.blue()].map(|c| (linear_to_srgb(*c) * max).round())
+    }
     pub fn maybe_new(v: Vec3) -> Option<Self> {
         if !((0.0..=1.0).contains(v.x())) {
             eprintln!("red is out of range");
@@ -109,6 +131,23 @@ impl Color {
         Some(Color(v))
     }
 }
+impl TryFrom<Vec3> for Color {
+    type Error = String;
+
+    fn try_from(value: Vec3) -> Result<Self, Self::Error> {
+        for (name, channel) in [("red", value.x), ("green", value.y), ("blue", value.z)] {
+            if !(0.0..=1.0).contains(&channel) {
+                return Err(format!("{} is out of range 0-1 value is:{}", name, channel));
+            }
+        }
+        Ok(Self(value))
+    }
+}
+impl From<Color> for Vec3 {
+    fn from(value: Color) -> Self {
+        value.0
+    }
+}
 impl Neg for &Color {
     type Output = Color;
 
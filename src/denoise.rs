@@ -0,0 +1,161 @@
+//! Cleaning up the noise of renders with few samples.
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    film::{Aovs, Film},
+    vector::Vec3,
+};
+
+/// Albedo below this is not divided out, there is nothing to recover there.
+const MIN_ALBEDO: f64 = 0.01;
+
+/// A joint bilateral filter that smooths monte carlo noise while keeping the edges
+/// found in the albedo, normal and depth buffers of the film.
+///
+/// Lighting is filtered with the albedo divided out and multiplied back afterwards,
+/// so texture detail survives even at high strengths.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Denoiser {
+    radius: usize,
+    strength: f64,
+    normal_sigma: f64,
+    albedo_sigma: f64,
+    depth_sigma: f64,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self {
+            radius: 5,
+            strength: 1.0,
+            normal_sigma: 0.3,
+            albedo_sigma: 0.1,
+            depth_sigma: 0.05,
+        }
+    }
+}
+
+impl Denoiser {
+    /// How far apart colors may be and still get averaged, 0 turns the filter off.
+    pub const fn set_strength(mut self, strength: f64) -> Self {
+        self.strength = strength;
+        self
+    }
+
+    /// Half the width of the filter window in pixels.
+    pub const fn set_radius(mut self, radius: usize) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    pub const fn strength(&self) -> f64 {
+        self.strength
+    }
+
+    pub const fn radius(&self) -> usize {
+        self.radius
+    }
+
+    /// Films without aovs are only filtered on color, which blurs edges a lot more.
+    pub fn denoise(&self, film: &Film) -> Film {
+        if self.strength <= 0.0 || self.radius == 0 {
+            return film.clone();
+        }
+        let (width, height) = (film.width(), film.height());
+        let aovs = film.aovs();
+        let albedo = |i: usize| match aovs {
+            Some(aovs) => demodulation(&aovs.albedo[i]),
+            None => Vec3::new(1.0, 1.0, 1.0),
+        };
+        let irradiance: Vec<Vec3> = film
+            .pixels()
+            .iter()
+            .enumerate()
+            .map(|(i, c)| divide(c, &albedo(i)))
+            .collect();
+
+        let rows: Vec<Vec<Vec3>> = (0..height)
+            .into_par_iter()
+            .map(|y| {
+                (0..width)
+                    .map(|x| {
+                        let filtered = self.filter_pixel(x, y, width, height, &irradiance, aovs);
+                        &filtered * &albedo(y * width + x)
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut denoised = Film::from_pixels(width, height, rows.concat());
+        if let Some(aovs) = aovs {
+            denoised.set_aovs(aovs.clone());
+        }
+        denoised
+    }
+
+    fn filter_pixel(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        irradiance: &[Vec3],
+        aovs: Option<&Aovs>,
+    ) -> Vec3 {
+        let center = y * width + x;
+        let spatial_sigma = self.radius as f64 / 2.0;
+        let color_sigma = 0.1 * self.strength;
+        let center_color = compress(&irradiance[center]);
+
+        let mut sum = Vec3::ZERO;
+        let mut weight_sum = 0.0;
+        for qy in y.saturating_sub(self.radius)..(y + self.radius + 1).min(height) {
+            for qx in x.saturating_sub(self.radius)..(x + self.radius + 1).min(width) {
+                let q = qy * width + qx;
+                let dx = qx as f64 - x as f64;
+                let dy = qy as f64 - y as f64;
+                let mut exponent = (dx * dx + dy * dy) / (2.0 * spatial_sigma * spatial_sigma);
+                let color_distance = (compress(&irradiance[q]) - center_color).length_squared();
+                exponent += color_distance / (2.0 * color_sigma * color_sigma);
+                if let Some(aovs) = aovs {
+                    exponent += self.guide_exponent(aovs, center, q);
+                }
+                let weight = (-exponent).exp();
+                sum += irradiance[q] * weight;
+                weight_sum += weight;
+            }
+        }
+        sum / weight_sum
+    }
+
+    fn guide_exponent(&self, aovs: &Aovs, p: usize, q: usize) -> f64 {
+        let normal = (aovs.normal[p] - aovs.normal[q]).length_squared()
+            / (2.0 * self.normal_sigma * self.normal_sigma);
+        let albedo = (aovs.albedo[p] - aovs.albedo[q]).length_squared()
+            / (2.0 * self.albedo_sigma * self.albedo_sigma);
+        let (dp, dq) = (aovs.depth[p], aovs.depth[q]);
+        let depth = match (dp.is_finite(), dq.is_finite()) {
+            (true, true) => ((dp - dq) / (self.depth_sigma * dp.max(1e-3))).powi(2) / 2.0,
+            (false, false) => 0.0,
+            _ => f64::INFINITY,
+        };
+        normal + albedo + depth
+    }
+}
+
+const fn demodulation(albedo: &Vec3) -> Vec3 {
+    Vec3::new(
+        albedo.x.max(MIN_ALBEDO),
+        albedo.y.max(MIN_ALBEDO),
+        albedo.z.max(MIN_ALBEDO),
+    )
+}
+
+fn divide(a: &Vec3, b: &Vec3) -> Vec3 {
+    Vec3::new(a.x / b.x, a.y / b.y, a.z / b.z)
+}
+
+/// Keeps fireflies from dominating the color distance.
+fn compress(c: &Vec3) -> Vec3 {
+    Vec3::new(c.x / (1.0 + c.x), c.y / (1.0 + c.y), c.z / (1.0 + c.z))
+}
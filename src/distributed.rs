@@ -0,0 +1,445 @@
+//! Rendering one image on many worker processes over TCP.
+//!
+//! Workers connect to a [`Coordinator`], which sends them the scene once and then
+//! hands out tiles in batches of one tile per worker thread. Workers render a batch
+//! in parallel with the same code as a local render and send the sample sums back,
+//! so the merged image is identical to one rendered in a single process with the
+//! same seed. While no worker is connected the coordinator renders tiles itself.
+//! Files the scene names, like lens prescriptions, are sent as absolute paths and
+//! have to exist there on the workers' machines too.
+
+use std::{
+    collections::VecDeque,
+    io::{self, BufReader, BufWriter, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    ops::Range,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+
+use crate::{
+    progress::{CancellationToken, ProgressObserver, Tracker},
+    render_state::{self, RenderState, TileState},
+    scene::Scene,
+    tiles::Tile,
+    world::SharedWorld,
+};
+
+const HELLO: &[u8; 8] = b"RTWORK02";
+const DONE: u8 = 0;
+const TILES: u8 = 1;
+/// How long idle workers and the accept loop wait before looking again.
+const POLL: Duration = Duration::from_millis(20);
+/// How long a connection may take to say hello before it is dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a worker may be silent on top of [`SAMPLE_TIMEOUT`] for every sample
+/// of a batch before its tiles are handed to others.
+const BATCH_TIMEOUT: Duration = Duration::from_secs(60);
+/// Generous for a single thread, and workers render a batch on many.
+const SAMPLE_TIMEOUT: Duration = Duration::from_micros(10);
+/// The most tiles sent in one batch, however many threads a worker claims to have.
+const MAX_BATCH: u64 = 256;
+
+/// Hands out the tiles of a render to the workers that connect to it.
+pub struct Coordinator {
+    listener: TcpListener,
+    verbose: bool,
+}
+
+impl Coordinator {
+    /// Listens on `address`, port 0 picks a free port, see [`Coordinator::local_addr`].
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            verbose: true,
+        })
+    }
+
+    /// Whether to print workers dropping out to stderr.
+    pub const fn set_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Renders `scene` into `state` up to the samples per pixel of its camera, with
+    /// as many workers as connect. Workers may come and go, the tiles of a worker
+    /// that drops out or stalls are handed to the others, or rendered here while
+    /// there are none. Returns once every tile is done or `cancel` is cancelled.
+    pub fn render(
+        &self,
+        scene: &Scene,
+        state: &RenderState,
+        observer: &dyn ProgressObserver,
+        cancel: &CancellationToken,
+    ) -> io::Result<()> {
+        let job = Job {
+            scene: scene.to_toml(),
+            seed: state.seed(),
+            aovs: state.has_aovs(),
+            target: scene.camera.samples_per_pixel(),
+        };
+        let queue = Mutex::new((0..state.tiles().len()).collect::<VecDeque<_>>());
+        let remaining = AtomicUsize::new(state.tiles().len());
+        let tracker = Tracker::start(observer, state.tiles().len());
+        let shared = Shared {
+            job: &job,
+            state,
+            queue: &queue,
+            remaining: &remaining,
+            tracker: &tracker,
+            cancel,
+            connected: AtomicUsize::new(0),
+        };
+        let world: SharedWorld = (&scene.world).into();
+        thread::scope(|scope| -> io::Result<()> {
+            while remaining.load(Ordering::Relaxed) > 0 && !cancel.is_cancelled() {
+                match self.listener.accept() {
+                    Ok((stream, address)) => {
+                        let shared = &shared;
+                        let verbose = self.verbose;
+                        scope.spawn(move || {
+                            if let Err(e) = shared.serve(stream) {
+                                if verbose {
+                                    eprintln!("worker {} dropped out: {}", address, e);
+                                }
+                            }
+                        });
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        let rendered = match shared.connected.load(Ordering::Relaxed) {
+                            0 => shared.render_locally(scene, &world),
+                            _ => false,
+                        };
+                        if !rendered {
+                            thread::sleep(POLL);
+                        }
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(())
+        })?;
+        tracker.finish();
+        Ok(())
+    }
+}
+
+/// What every worker needs to know before it can render tiles.
+struct Job {
+    scene: String,
+    seed: u64,
+    aovs: bool,
+    target: usize,
+}
+
+struct Shared<'a> {
+    job: &'a Job,
+    state: &'a RenderState,
+    queue: &'a Mutex<VecDeque<usize>>,
+    remaining: &'a AtomicUsize,
+    tracker: &'a Tracker<'a>,
+    cancel: &'a CancellationToken,
+    /// Workers past the handshake.
+    connected: AtomicUsize,
+}
+
+/// A tile handed out to be rendered, with the samples it still needs.
+struct Assigned {
+    index: usize,
+    tile: Tile,
+    samples: Range<usize>,
+}
+
+impl Shared<'_> {
+    /// Feeds one worker tiles until there are none left.
+    fn serve(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_nodelay(true)?;
+        // a connection that never says hello must not hold up the render
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        stream.set_write_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        let mut input = BufReader::new(stream.try_clone()?);
+        let mut out = BufWriter::new(stream);
+        let mut hello = [0; 8];
+        input.read_exact(&mut hello).map_err(|e| match e.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => io::Error::new(
+                io::ErrorKind::TimedOut,
+                "no hello within the handshake timeout",
+            ),
+            _ => e,
+        })?;
+        if &hello != HELLO {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a raytracer worker",
+            ));
+        }
+        // take never hands out more tiles than are queued
+        let batch = read_u64(&mut input)?.clamp(1, MAX_BATCH) as usize;
+        write_bytes(&mut out, self.job.scene.as_bytes())?;
+        out.write_all(&self.job.seed.to_le_bytes())?;
+        out.write_all(&[self.job.aovs as u8])?;
+        out.flush()?;
+
+        self.connected.fetch_add(1, Ordering::Relaxed);
+        let fed = self.feed(&mut input, &mut out, batch);
+        self.connected.fetch_sub(1, Ordering::Relaxed);
+        fed
+    }
+
+    fn feed(
+        &self,
+        input: &mut BufReader<TcpStream>,
+        out: &mut impl Write,
+        batch: usize,
+    ) -> io::Result<()> {
+        while self.remaining.load(Ordering::Relaxed) > 0 && !self.cancel.is_cancelled() {
+            let assigned = self.take(batch);
+            if assigned.is_empty() {
+                // other workers still have tiles out, one of them might drop out
+                thread::sleep(POLL);
+                continue;
+            }
+            self.render_remotely(input, out, &assigned)?;
+        }
+        out.write_all(&[DONE])?;
+        out.flush()
+    }
+
+    /// Takes up to `count` tiles off the queue, tiles that already have all their
+    /// samples are counted as done right away.
+    fn take(&self, count: usize) -> Vec<Assigned> {
+        let mut queue = lock(self.queue);
+        let mut assigned = Vec::new();
+        while assigned.len() < count {
+            let Some(index) = queue.pop_front() else {
+                break;
+            };
+            let (tile, first) = {
+                let tile_state = render_state::lock(&self.state.tiles()[index]);
+                (tile_state.tile, tile_state.samples)
+            };
+            match first < self.job.target {
+                true => assigned.push(Assigned {
+                    index,
+                    tile,
+                    samples: first..self.job.target,
+                }),
+                false => {
+                    self.remaining.fetch_sub(1, Ordering::Relaxed);
+                }
+            }
+        }
+        assigned
+    }
+
+    fn finish(&self, assigned: &Assigned, rendered: TileState) {
+        render_state::lock(&self.state.tiles()[assigned.index]).merge(rendered);
+        self.remaining.fetch_sub(1, Ordering::Relaxed);
+        self.tracker
+            .add((assigned.tile.area() * assigned.samples.len()) as u64);
+    }
+
+    /// Puts tiles that were not rendered back at the front of the queue.
+    fn give_back(&self, assigned: &[Assigned]) {
+        let mut queue = lock(self.queue);
+        for assigned in assigned.iter().rev() {
+            queue.push_front(assigned.index);
+        }
+    }
+
+    /// Renders a batch of tiles on this machine, false when there were none left.
+    fn render_locally(&self, scene: &Scene, world: &SharedWorld) -> bool {
+        let assigned = self.take(rayon::current_num_threads());
+        assigned.par_iter().for_each(|assigned| {
+            let rendered = scene.camera.render_tile(
+                world,
+                self.job.seed,
+                assigned.tile,
+                assigned.samples.clone(),
+                self.job.aovs,
+            );
+            self.finish(assigned, rendered);
+        });
+        !assigned.is_empty()
+    }
+
+    fn render_remotely(
+        &self,
+        input: &mut BufReader<TcpStream>,
+        out: &mut impl Write,
+        assigned: &[Assigned],
+    ) -> io::Result<()> {
+        // a stalled worker must not keep its tiles forever
+        let samples: usize = assigned
+            .iter()
+            .map(|assigned| assigned.tile.area() * assigned.samples.len())
+            .sum();
+        let timeout = u32::try_from(samples)
+            .ok()
+            .and_then(|samples| SAMPLE_TIMEOUT.checked_mul(samples))
+            .and_then(|timeout| timeout.checked_add(BATCH_TIMEOUT))
+            .unwrap_or(Duration::MAX);
+        if let Err(e) = input
+            .get_ref()
+            .set_read_timeout(Some(timeout))
+            .and_then(|()| send_tiles(out, assigned))
+        {
+            self.give_back(assigned);
+            return Err(e);
+        }
+        let size = (self.state.width(), self.state.height());
+        for (i, tile) in assigned.iter().enumerate() {
+            let rendered = TileState::read(input, self.job.aovs, size).and_then(|rendered| {
+                match rendered.tile == tile.tile && rendered.samples == tile.samples.end {
+                    true => Ok(rendered),
+                    false => Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "worker sent back a different tile",
+                    )),
+                }
+            });
+            let rendered = rendered.map_err(|e| match e.kind() {
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("no tiles back within {:?}", timeout),
+                ),
+                _ => e,
+            });
+            match rendered {
+                Ok(rendered) => self.finish(tile, rendered),
+                Err(e) => {
+                    self.give_back(&assigned[i..]);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn send_tiles(out: &mut impl Write, assigned: &[Assigned]) -> io::Result<()> {
+    out.write_all(&[TILES])?;
+    out.write_all(&(assigned.len() as u64).to_le_bytes())?;
+    for assigned in assigned {
+        let Tile {
+            x,
+            y,
+            width,
+            height,
+        } = assigned.tile;
+        for v in [
+            x,
+            y,
+            width,
+            height,
+            assigned.samples.start,
+            assigned.samples.end,
+        ] {
+            out.write_all(&(v as u64).to_le_bytes())?;
+        }
+    }
+    out.flush()
+}
+
+/// Connects to a coordinator and renders the tiles it hands out until it is done,
+/// returning how many tiles were rendered. Batches are as large as the rayon
+/// thread pool and rendered on all of its threads.
+pub fn work(coordinator: impl ToSocketAddrs) -> io::Result<usize> {
+    let stream = TcpStream::connect(coordinator)?;
+    stream.set_nodelay(true)?;
+    let mut input = BufReader::new(stream.try_clone()?);
+    let mut out = BufWriter::new(stream);
+    out.write_all(HELLO)?;
+    out.write_all(&(rayon::current_num_threads() as u64).to_le_bytes())?;
+    out.flush()?;
+
+    let source = String::from_utf8(read_bytes(&mut input)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let scene = Scene::from_toml(&source)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    let seed = read_u64(&mut input)?;
+    let mut aovs = [0];
+    input.read_exact(&mut aovs)?;
+    let aovs = aovs[0] != 0;
+    let world: SharedWorld = (&scene.world).into();
+
+    let mut tiles = 0;
+    loop {
+        let mut message = [0];
+        input.read_exact(&mut message)?;
+        match message[0] {
+            DONE => return Ok(tiles),
+            TILES => {
+                let mut next = || read_u64(&mut input).map(|v| v as usize);
+                let count = next()?;
+                let mut batch = Vec::new();
+                for _ in 0..count {
+                    let tile = Tile {
+                        x: next()?,
+                        y: next()?,
+                        width: next()?,
+                        height: next()?,
+                    };
+                    batch.push((tile, next()?..next()?));
+                }
+                let rendered: Vec<TileState> = batch
+                    .into_par_iter()
+                    .map(|(tile, samples)| {
+                        scene.camera.render_tile(&world, seed, tile, samples, aovs)
+                    })
+                    .collect();
+                for rendered in &rendered {
+                    rendered.write(&mut out)?;
+                }
+                out.flush()?;
+                tiles += count;
+            }
+            other => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown message {} from the coordinator", other),
+                ))
+            }
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn write_bytes(out: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    out.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    out.write_all(bytes)
+}
+
+fn read_bytes(input: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = read_u64(input)?;
+    let mut bytes = Vec::new();
+    input.take(len).read_to_end(&mut bytes)?;
+    match bytes.len() as u64 == len {
+        true => Ok(bytes),
+        false => Err(io::ErrorKind::UnexpectedEof.into()),
+    }
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
@@ -0,0 +1,161 @@
+//! The pixels a render produces.
+
+use crate::vector::Vec3;
+
+/// The linear radiance gathered by a render, one averaged sample per pixel.
+#[derive(Clone, Debug)]
+pub struct Film {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3>,
+    aovs: Option<Aovs>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Vec3::ZERO; width * height],
+            aovs: None,
+        }
+    }
+
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Vec3>) -> Self {
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "film needs {}x{} pixels but got {}",
+            width,
+            height,
+            pixels.len()
+        );
+        Self {
+            width,
+            height,
+            pixels,
+            aovs: None,
+        }
+    }
+
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> &Vec3 {
+        &self.pixels[y * self.width + x]
+    }
+
+    pub fn pixel_mut(&mut self, x: usize, y: usize) -> &mut Vec3 {
+        &mut self.pixels[y * self.width + x]
+    }
+
+    pub fn pixels(&self) -> &[Vec3] {
+        &self.pixels
+    }
+
+    /// A film of the same size with `f` applied to every pixel, aovs are kept.
+    pub fn map(&self, f: impl Fn(&Vec3) -> Vec3) -> Self {
+        Self {
+            width: self.width,
+            height: self.height,
+            pixels: self.pixels.iter().map(f).collect(),
+            aovs: self.aovs.clone(),
+        }
+    }
+
+    /// The `width` by `height` pixels starting at (`x`, `y`), with their aovs.
+    pub fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Self {
+        assert!(
+            x + width <= self.width && y + height <= self.height,
+            "cannot crop {}x{} pixels at {},{} out of a {}x{} film",
+            width,
+            height,
+            x,
+            y,
+            self.width,
+            self.height
+        );
+        let indices = || {
+            (y..y + height)
+                .flat_map(move |row| (x..x + width).map(move |column| row * self.width + column))
+        };
+        let aovs = self.aovs.as_ref().map(|aovs| Aovs {
+            depth: indices().map(|i| aovs.depth[i]).collect(),
+            normal: indices().map(|i| aovs.normal[i]).collect(),
+            albedo: indices().map(|i| aovs.albedo[i]).collect(),
+            object_id: indices().map(|i| aovs.object_id[i]).collect(),
+        });
+        Self {
+            width,
+            height,
+            pixels: indices().map(|i| self.pixels[i]).collect(),
+            aovs,
+        }
+    }
+
+    /// Rows from top to bottom, each `width` pixels long.
+    pub fn rows(&self) -> impl Iterator<Item = &[Vec3]> {
+        self.pixels.chunks(self.width.max(1))
+    }
+}
+
+impl Film {
+    /// The first hit buffers, only there when the camera was asked for them.
+    pub const fn aovs(&self) -> Option<&Aovs> {
+        self.aovs.as_ref()
+    }
+
+    pub fn set_aovs(&mut self, aovs: Aovs) {
+        assert_eq!(
+            aovs.depth.len(),
+            self.pixels.len(),
+            "aovs do not match the film size"
+        );
+        self.aovs = Some(aovs);
+    }
+}
+
+/// What the camera rays hit first, averaged over the samples of a pixel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AovSample {
+    /// Distance from the ray origin, infinite when nothing was hit.
+    pub depth: f64,
+    pub normal: Vec3,
+    pub albedo: Vec3,
+    /// Index of the shape in the world plus one, 0 is the background.
+    pub object_id: u32,
+}
+
+impl AovSample {
+    pub const BACKGROUND: Self = Self {
+        depth: f64::INFINITY,
+        normal: Vec3::ZERO,
+        albedo: Vec3::ZERO,
+        object_id: 0,
+    };
+}
+
+/// Arbitrary output variables stored next to the beauty pass.
+#[derive(Clone, Debug, Default)]
+pub struct Aovs {
+    pub depth: Vec<f64>,
+    pub normal: Vec<Vec3>,
+    pub albedo: Vec<Vec3>,
+    pub object_id: Vec<u32>,
+}
+
+impl Extend<AovSample> for Aovs {
+    fn extend<T: IntoIterator<Item = AovSample>>(&mut self, iter: T) {
+        for sample in iter {
+            self.depth.push(sample.depth);
+            self.normal.push(sample.normal);
+            self.albedo.push(sample.albedo);
+            self.object_id.push(sample.object_id);
+        }
+    }
+}
@@ -0,0 +1,321 @@
+//! Scenes that can be rendered without a scene file.
+
+use crate::{
+    camera::Camera,
+    color::Color,
+    materials::{Dielectric, DiffuseLight, Lambertian, Materials, Metal},
+    random::{self, SampleRng},
+    scene::Scene,
+    shapes::{Quad, Sphere},
+    textures::{Checker, Noise},
+    vector::{Point3D, Vec3},
+    world::{Environment, World},
+};
+
+/// A scene that ships with the renderer, built together with the camera that suits it.
+pub struct BuiltinScene {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Scenes with random content are reproducible for the same seed.
+    pub build: fn(Option<u64>) -> Scene,
+}
+
+pub const SCENES: &[BuiltinScene] = &[
+    BuiltinScene {
+        name: "random-spheres",
+        description: "the cover of Ray Tracing in One Weekend, with bouncing spheres",
+        build: random_spheres,
+    },
+    BuiltinScene {
+        name: "cornell-box",
+        description: "the Cornell box with two rotated blocks under an area light",
+        build: cornell_box,
+    },
+    BuiltinScene {
+        name: "checkered-spheres",
+        description: "two large spheres with a checker texture",
+        build: checkered_spheres,
+    },
+    BuiltinScene {
+        name: "perlin-spheres",
+        description: "marble like Perlin noise on a ground and a small sphere",
+        build: perlin_spheres,
+    },
+    BuiltinScene {
+        name: "simple-light",
+        description: "the Perlin spheres lit only by a rectangle and a sphere light",
+        build: simple_light,
+    },
+    BuiltinScene {
+        name: "motion-blur",
+        description: "spheres moving at different speeds during the shutter interval",
+        build: motion_blur,
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static BuiltinScene> {
+    SCENES.iter().find(|scene| scene.name == name)
+}
+
+/// Looks up a scene by name and builds it.
+pub fn build(name: &str, seed: Option<u64>) -> Option<Scene> {
+    find(name).map(|scene| (scene.build)(seed))
+}
+
+fn lambertian(red: f64, green: f64, blue: f64) -> Materials {
+    Lambertian::new(Color::new(Vec3::new(red, green, blue))).into()
+}
+
+fn random_spheres(seed: Option<u64>) -> Scene {
+    let mut world = World::new();
+    let ground_material: Materials = Lambertian::new(Color::new(Vec3::new(0.5, 0.5, 0.5))).into();
+    world.add_shape(Sphere::new(
+        Point3D::new(0.0, -1000.0, 0.0),
+        1000.0,
+        ground_material,
+    ));
+
+    let mut between = random::sample_rng(seed.unwrap_or_else(random::entropy_seed));
+
+    let mut shapes = Vec::new();
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = between.next().unwrap_or_default();
+            let center = Point3D::new(
+                a as f64 + 0.9 * between.next().unwrap_or_default(),
+                0.2,
+                b as f64 + 0.9 * between.next().unwrap_or_default(),
+            );
+
+            if (center - Point3D::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                let sphere: Sphere = if choose_mat < 0.8 {
+                    let color = random_color(&mut between) * random_color(&mut between);
+                    let mat = Lambertian::new(color).into();
+                    let center2 =
+                        center + Vec3::new(0.0, between.next().unwrap_or_default() * 0.5, 0.0);
+                    Sphere::new_moving(center, center2, 0.2, mat)
+                } else if choose_mat < 0.95 {
+                    let color = &random_color(&mut between) * 0.5 + 0.5_f64 * &Color::WHITE;
+                    let fuzz = between.next().unwrap_or_default() * 0.5;
+                    let mat = Metal::new(color, fuzz).into();
+                    Sphere::new(center, 0.2, mat)
+                } else {
+                    let mat = Dielectric::new(1.5).into();
+                    Sphere::new(center, 0.2, mat)
+                };
+
+                shapes.push(sphere);
+            }
+        }
+    }
+    world.add_shapes(shapes);
+    let material = Dielectric::new(1.5).into();
+    world.add_shape(Sphere::new(Point3D::new(0.0, 1.0, 0.0), 1.0, material));
+
+    let material = Lambertian::new(Color::new(Vec3::new(0.4, 0.2, 0.1))).into();
+    world.add_shape(Sphere::new(Point3D::new(-4.0, 1.0, 0.0), 1.0, material));
+
+    let material = Metal::new(Color::new(Vec3::new(0.7, 0.6, 0.5)), 0.0).into();
+    world.add_shape(Sphere::new(Point3D::new(4.0, 1.0, 0.0), 1.0, material));
+
+    let camera = Camera::default()
+        .set_image_width_with_aspect_ratio(400, 16.0 / 9.0)
+        .set_samples_per_pixel(100)
+        .set_vfov(20.0)
+        .set_camera_center(Point3D::new(13.0, 2.0, 3.0))
+        .set_look_at(Vec3::new(0.0, 0.0, 0.0))
+        .set_defocus_angle(0.6)
+        .set_focus_dist(10.0);
+    Scene { world, camera }
+}
+fn random_color(between: &mut SampleRng) -> Color {
+    Color::new(Vec3::new(
+        between.next().unwrap_or_default(),
+        between.next().unwrap_or_default(),
+        between.next().unwrap_or_default(),
+    ))
+}
+
+fn cornell_box(_seed: Option<u64>) -> Scene {
+    let red = lambertian(0.65, 0.05, 0.05);
+    let white = lambertian(0.73, 0.73, 0.73);
+    let green = lambertian(0.12, 0.45, 0.15);
+    let light: Materials = DiffuseLight::new(Vec3::new(15.0, 15.0, 15.0)).into();
+
+    let mut world = World::new();
+    world.set_environment(Environment::Solid { color: Vec3::ZERO });
+    world.add_shapes([
+        Quad::new(
+            Point3D::new(555.0, 0.0, 0.0),
+            Vec3::new(0.0, 555.0, 0.0),
+            Vec3::new(0.0, 0.0, 555.0),
+            green,
+        ),
+        Quad::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 555.0, 0.0),
+            Vec3::new(0.0, 0.0, 555.0),
+            red,
+        ),
+        Quad::new(
+            Point3D::new(343.0, 554.0, 332.0),
+            Vec3::new(-130.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -105.0),
+            light,
+        ),
+        Quad::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Vec3::new(555.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 555.0),
+            white.clone(),
+        ),
+        Quad::new(
+            Point3D::new(555.0, 555.0, 555.0),
+            Vec3::new(-555.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -555.0),
+            white.clone(),
+        ),
+        Quad::new(
+            Point3D::new(0.0, 0.0, 555.0),
+            Vec3::new(555.0, 0.0, 0.0),
+            Vec3::new(0.0, 555.0, 0.0),
+            white.clone(),
+        ),
+    ]);
+    let tall_box = Quad::cuboid(
+        Point3D::new(0.0, 0.0, 0.0),
+        Point3D::new(165.0, 330.0, 165.0),
+        white.clone(),
+    );
+    world.add_shapes(
+        tall_box
+            .into_iter()
+            .map(|side| side.rotate_y(15.0).translate(Vec3::new(265.0, 0.0, 295.0))),
+    );
+    let short_box = Quad::cuboid(
+        Point3D::new(0.0, 0.0, 0.0),
+        Point3D::new(165.0, 165.0, 165.0),
+        white,
+    );
+    world.add_shapes(
+        short_box
+            .into_iter()
+            .map(|side| side.rotate_y(-18.0).translate(Vec3::new(130.0, 0.0, 65.0))),
+    );
+
+    let camera = Camera::default()
+        .set_image_width_with_aspect_ratio(600, 1.0)
+        .set_samples_per_pixel(200)
+        .set_vfov(40.0)
+        .set_camera_center(Point3D::new(278.0, 278.0, -800.0))
+        .set_look_at(Point3D::new(278.0, 278.0, 0.0))
+        .set_defocus_angle(0.0);
+    Scene { world, camera }
+}
+
+fn checkered_spheres(_seed: Option<u64>) -> Scene {
+    let checker: Materials = Lambertian::from_texture(Checker::new(
+        0.32,
+        Color::new(Vec3::new(0.2, 0.3, 0.1)),
+        Color::new(Vec3::new(0.9, 0.9, 0.9)),
+    ))
+    .into();
+    let mut world = World::new();
+    world.add_shape(Sphere::new(
+        Point3D::new(0.0, -10.0, 0.0),
+        10.0,
+        checker.clone(),
+    ));
+    world.add_shape(Sphere::new(Point3D::new(0.0, 10.0, 0.0), 10.0, checker));
+    Scene {
+        world,
+        camera: textured_camera(),
+    }
+}
+
+fn perlin_spheres(_seed: Option<u64>) -> Scene {
+    let mut world = World::new();
+    add_perlin_spheres(&mut world);
+    Scene {
+        world,
+        camera: textured_camera(),
+    }
+}
+
+fn simple_light(_seed: Option<u64>) -> Scene {
+    let mut world = World::new();
+    add_perlin_spheres(&mut world);
+    let light: Materials = DiffuseLight::new(Vec3::new(4.0, 4.0, 4.0)).into();
+    world.add_shape(Quad::new(
+        Point3D::new(3.0, 1.0, -2.0),
+        Vec3::new(2.0, 0.0, 0.0),
+        Vec3::new(0.0, 2.0, 0.0),
+        light.clone(),
+    ));
+    world.add_shape(Sphere::new(Point3D::new(0.0, 7.0, 0.0), 2.0, light));
+    world.set_environment(Environment::Solid { color: Vec3::ZERO });
+
+    let camera = textured_camera()
+        .set_camera_center(Point3D::new(26.0, 3.0, 6.0))
+        .set_look_at(Point3D::new(0.0, 2.0, 0.0));
+    Scene { world, camera }
+}
+
+fn motion_blur(_seed: Option<u64>) -> Scene {
+    let ground: Materials = Lambertian::from_texture(Checker::new(
+        0.5,
+        Color::new(Vec3::new(0.2, 0.3, 0.1)),
+        Color::new(Vec3::new(0.9, 0.9, 0.9)),
+    ))
+    .into();
+    let mut world = World::new();
+    world.add_shape(Sphere::new(Point3D::new(0.0, -1000.0, 0.0), 1000.0, ground));
+    // the same sphere at increasing speeds, from standing still to a long streak
+    let colors = [
+        lambertian(0.8, 0.2, 0.2),
+        lambertian(0.8, 0.6, 0.2),
+        lambertian(0.2, 0.6, 0.3),
+        lambertian(0.2, 0.4, 0.8),
+    ];
+    for (i, material) in colors.into_iter().enumerate() {
+        let center = Point3D::new(-1.5, 0.5, 1.5 - 1.2 * i as f64);
+        let speed = i as f64 * 0.5;
+        world.add_shape(Sphere::new_moving(
+            center,
+            center + Vec3::new(speed, 0.0, 0.0),
+            0.5,
+            material,
+        ));
+    }
+
+    let camera = Camera::default()
+        .set_image_width_with_aspect_ratio(400, 16.0 / 9.0)
+        .set_samples_per_pixel(100)
+        .set_vfov(30.0)
+        .set_camera_center(Point3D::new(0.0, 3.0, 8.0))
+        .set_look_at(Point3D::new(0.0, 0.5, 0.0))
+        .set_defocus_angle(0.0);
+    Scene { world, camera }
+}
+
+fn add_perlin_spheres(world: &mut World) {
+    let marble: Materials = Lambertian::from_texture(Noise::new(4.0)).into();
+    world.add_shape(Sphere::new(
+        Point3D::new(0.0, -1000.0, 0.0),
+        1000.0,
+        marble.clone(),
+    ));
+    world.add_shape(Sphere::new(Point3D::new(0.0, 2.0, 0.0), 2.0, marble));
+}
+
+/// The view of the texture scenes in Ray Tracing: The Next Week.
+fn textured_camera() -> Camera {
+    Camera::default()
+        .set_image_width_with_aspect_ratio(400, 16.0 / 9.0)
+        .set_samples_per_pixel(100)
+        .set_vfov(20.0)
+        .set_camera_center(Point3D::new(13.0, 2.0, 3.0))
+        .set_look_at(Point3D::new(0.0, 0.0, 0.0))
+        .set_defocus_angle(0.0)
+}
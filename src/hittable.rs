@@ -1,8 +1,11 @@
+//! Ray intersections.
+
 use crate::{
     materials::Materials,
     ray::Ray,
     vector::{Point3D, Vec3},
 };
+/// Where a ray hit a surface, with the normal facing against the ray.
 pub struct Hit {
     pub p: Point3D,
     pub normal: Vec3,
@@ -27,6 +30,7 @@ impl Hit {
         }
     }
 }
+/// Anything a ray can hit.
 pub trait Hittable {
     fn hit(&self, r: &Ray) -> Option<Hit>;
 }
@@ -0,0 +1,51 @@
+//! A path tracer after the Ray Tracing in One Weekend books.
+//!
+//! Put [`shapes`] with [`materials`] into a [`World`], point a [`Camera`] at it and
+//! call [`Camera::render`] to get a [`Film`] of linear radiance, which
+//! [`output::Output`] writes to disk:
+//!
+//! ```no_run
+//! use raytracer::{
+//!     materials::Lambertian, output::Output, shapes::Sphere, Camera, Color, Point3D, Vec3,
+//!     World,
+//! };
+//!
+//! let mut world = World::new();
+//! let red = Lambertian::new(Color::new(Vec3::new(0.8, 0.1, 0.1)));
+//! world.add_shape(Sphere::new(Point3D::new(0.0, 0.0, -1.0), 0.5, red.into()));
+//! let film = Camera::default().set_samples_per_pixel(50).render(&world);
+//! Output::file("sphere.png").unwrap().write(&film).unwrap();
+//! ```
+//!
+//! Whole scenes, camera included, can also be read from TOML with [`Scene::load`] or
+//! taken from the [`gallery`].
+#![warn(clippy::missing_const_for_fn, clippy::perf)]
+
+pub mod camera;
+pub mod color;
+pub mod denoise;
+pub mod distributed;
+pub mod film;
+pub mod gallery;
+pub mod hittable;
+pub mod materials;
+pub mod output;
+pub mod progress;
+mod random;
+pub mod ray;
+pub mod render_state;
+pub mod scene;
+pub mod server;
+pub mod shapes;
+pub mod textures;
+pub mod tiles;
+pub mod tonemap;
+pub mod vector;
+pub mod world;
+
+pub use camera::Camera;
+pub use color::Color;
+pub use film::Film;
+pub use scene::Scene;
+pub use vector::{Point3D, Vec3};
+pub use world::World;
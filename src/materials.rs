@@ -1,19 +1,35 @@
+//! Surface materials, all gathered in the serializable [`Materials`] enum.
+
 mod dielectric;
+mod diffuse_light;
 mod lambertian;
 mod metal;
 
-use crate::{color::Color, hittable::Hit, ray::Ray};
+use serde::{Deserialize, Serialize};
+
+use crate::{color::Color, hittable::Hit, ray::Ray, vector::Vec3};
 pub use dielectric::Dielectric;
+pub use diffuse_light::DiffuseLight;
 pub use lambertian::Lambertian;
 pub use metal::Metal;
-#[derive(Clone, Debug)]
+/// Serialized as a table with a `type` key naming the material.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum Materials {
     Metal(Metal),
     Lambertian(Lambertian),
     Dielectric(Dielectric),
+    DiffuseLight(DiffuseLight),
 }
+/// How light scatters off a surface.
 pub trait Material: Into<Materials> {
     fn scatter(&self, r: &Ray, hit: &Hit) -> Option<(Ray, Color)>;
+    /// The surface color without any lighting, used for guide buffers.
+    fn albedo(&self, hit: &Hit) -> Color;
+    /// Light given off by the surface itself.
+    fn emitted(&self, _hit: &Hit) -> Vec3 {
+        Vec3::ZERO
+    }
 }
 
 impl Material for Materials {
@@ -22,6 +38,21 @@ impl Material for Materials {
             Materials::Metal(m) => m.scatter(r, hit),
             Materials::Lambertian(l) => l.scatter(r, hit),
             Materials::Dielectric(d) => d.scatter(r, hit),
+            Materials::DiffuseLight(l) => l.scatter(r, hit),
+        }
+    }
+    fn albedo(&self, hit: &Hit) -> Color {
+        match self {
+            Materials::Metal(m) => m.albedo(hit),
+            Materials::Lambertian(l) => l.albedo(hit),
+            Materials::Dielectric(d) => d.albedo(hit),
+            Materials::DiffuseLight(l) => l.albedo(hit),
+        }
+    }
+    fn emitted(&self, hit: &Hit) -> Vec3 {
+        match self {
+            Materials::DiffuseLight(l) => l.emitted(hit),
+            _ => Vec3::ZERO,
         }
     }
 }
@@ -40,3 +71,8 @@ impl From<Dielectric> for Materials {
         Materials::Dielectric(value)
     }
 }
+impl From<DiffuseLight> for Materials {
+    fn from(value: DiffuseLight) -> Self {
+        Materials::DiffuseLight(value)
+    }
+}
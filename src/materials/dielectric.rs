@@ -6,12 +6,43 @@ use crate::{
     vector::{random_f64_in_range, Vec3},
 };
 
+use serde::{Deserialize, Serialize};
+
 use super::Material;
-#[derive(Debug, Clone)]
+/// Deserializing fails unless the refraction index is positive.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "DielectricSettings", into = "DielectricSettings")]
 pub struct Dielectric {
     refraction_index: f64,
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DielectricSettings {
+    refraction_index: f64,
+}
+
+impl TryFrom<DielectricSettings> for Dielectric {
+    type Error = String;
+
+    fn try_from(value: DielectricSettings) -> Result<Self, Self::Error> {
+        match value.refraction_index > 0.0 {
+            true => Ok(Self::new(value.refraction_index)),
+            false => Err(format!(
+                "refraction_index needs to be positive, value is:{}",
+                value.refraction_index
+            )),
+        }
+    }
+}
+impl From<Dielectric> for DielectricSettings {
+    fn from(value: Dielectric) -> Self {
+        Self {
+            refraction_index: value.refraction_index,
+        }
+    }
+}
+
 impl Dielectric {
     pub const fn new(refraction_index: f64) -> Self {
         Self { refraction_index }
@@ -42,6 +73,9 @@ impl Material for Dielectric {
         let scatterd = Ray::new(hit.p, direction, r.tm());
         Some((scatterd, color))
     }
+    fn albedo(&self, _hit: &crate::hittable::Hit) -> Color {
+        Color::WHITE
+    }
 }
 fn reflectance(cosine: f64, refraction_index: f64) -> f64 {
     let r0 = (1.0 - refraction_index) / (1.0 + refraction_index);
@@ -0,0 +1,28 @@
+use crate::{color::Color, hittable::Hit, ray::Ray, vector::Vec3};
+
+use serde::{Deserialize, Serialize};
+
+use super::Material;
+/// Glows with `emit`, which may be brighter than 1, and reflects nothing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DiffuseLight {
+    emit: Vec3,
+}
+
+impl DiffuseLight {
+    pub const fn new(emit: Vec3) -> Self {
+        Self { emit }
+    }
+}
+impl Material for DiffuseLight {
+    fn scatter(&self, _r: &Ray, _hit: &Hit) -> Option<(Ray, Color)> {
+        None
+    }
+    fn albedo(&self, _hit: &Hit) -> Color {
+        Color::clamped(self.emit)
+    }
+    fn emitted(&self, _hit: &Hit) -> Vec3 {
+        self.emit
+    }
+}
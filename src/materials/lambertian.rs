@@ -1,14 +1,30 @@
-use crate::{color::Color, ray::Ray, vector::random_unit_in_disk};
+use crate::{
+    color::Color,
+    ray::Ray,
+    textures::{Texture, Textures},
+    vector::random_unit_in_disk,
+};
+
+use serde::{Deserialize, Serialize};
 
 use super::Material;
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Lambertian {
-    color: Color,
+    /// Either a plain color or a texture table.
+    albedo: Textures,
 }
 
 impl Lambertian {
     pub const fn new(color: Color) -> Self {
-        Self { color }
+        Self {
+            albedo: Textures::Solid(color),
+        }
+    }
+    pub fn from_texture(texture: impl Into<Textures>) -> Self {
+        Self {
+            albedo: texture.into(),
+        }
     }
 }
 impl Material for Lambertian {
@@ -19,6 +35,9 @@ impl Material for Lambertian {
         }
 
         let scatterd = Ray::new(hit.p, scatter_direction, r.tm());
-        Some((scatterd, self.color))
+        Some((scatterd, self.albedo.value(&hit.p)))
+    }
+    fn albedo(&self, hit: &crate::hittable::Hit) -> Color {
+        self.albedo.value(&hit.p)
     }
 }
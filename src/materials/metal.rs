@@ -1,12 +1,43 @@
 use crate::{color::Color, ray::Ray, vector::random_unit_in_disk};
 
+use serde::{Deserialize, Serialize};
+
 use super::Material;
-#[derive(Debug, Clone)]
+/// Deserializing fails unless the fuzz is in 0-1.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "MetalSettings", into = "MetalSettings")]
 pub struct Metal {
     color: Color,
     fuzz: f64,
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MetalSettings {
+    albedo: Color,
+    #[serde(default)]
+    fuzz: f64,
+}
+
+impl TryFrom<MetalSettings> for Metal {
+    type Error = String;
+
+    fn try_from(value: MetalSettings) -> Result<Self, Self::Error> {
+        match (0.0..=1.0).contains(&value.fuzz) {
+            true => Ok(Self::new(value.albedo, value.fuzz)),
+            false => Err(format!("fuzz is out of range 0-1 value is:{}", value.fuzz)),
+        }
+    }
+}
+impl From<Metal> for MetalSettings {
+    fn from(value: Metal) -> Self {
+        Self {
+            albedo: value.color,
+            fuzz: value.fuzz,
+        }
+    }
+}
+
 impl Metal {
     pub const fn new(color: Color, fuzz: f64) -> Self {
         Self { color, fuzz }
@@ -21,4 +52,7 @@ impl Material for Metal {
         }
         Some((Ray::new(hit.p, reflected, r.tm()), self.color))
     }
+    fn albedo(&self, _hit: &crate::hittable::Hit) -> Color {
+        self.color
+    }
 }
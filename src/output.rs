@@ -0,0 +1,266 @@
+//! Encoding films as image files.
+
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+mod exr;
+mod hdr;
+
+use crate::{color::Color, film::Film, tonemap::PostProcess};
+pub use exr::{ExrCompression, ExrPrecision, ExrSettings};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// ASCII `P3` portable pixmap
+    Ppm,
+    /// Binary `P6` portable pixmap
+    PpmBinary,
+    Png8,
+    Png16,
+    /// Scene referred OpenEXR with every aov of the film
+    Exr(ExrSettings),
+    /// Radiance RGBE
+    Hdr,
+    /// Portable float map
+    Pfm,
+}
+
+impl ImageFormat {
+    /// Guesses the format from the extension of `path`, `.png` is written with 8 bits.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ppm" => Some(Self::Ppm),
+            "png" => Some(Self::Png8),
+            "exr" => Some(Self::Exr(ExrSettings::default())),
+            "hdr" => Some(Self::Hdr),
+            "pfm" => Some(Self::Pfm),
+            _ => None,
+        }
+    }
+
+    pub fn write(&self, film: &Film, out: impl Write) -> io::Result<()> {
+        match self {
+            ImageFormat::Ppm => write_ppm(film, out),
+            ImageFormat::PpmBinary => write_ppm_binary(film, out),
+            ImageFormat::Png8 => write_png(film, out, png::BitDepth::Eight),
+            ImageFormat::Png16 => write_png(film, out, png::BitDepth::Sixteen),
+            ImageFormat::Exr(settings) => exr::write_exr(film, settings, out),
+            ImageFormat::Hdr => hdr::write_hdr(film, out),
+            ImageFormat::Pfm => hdr::write_pfm(film, out),
+        }
+    }
+
+    /// Whether the format stores display ready 0-1 values rather than scene radiance.
+    pub const fn is_display_referred(&self) -> bool {
+        matches!(
+            self,
+            ImageFormat::Ppm | ImageFormat::PpmBinary | ImageFormat::Png8 | ImageFormat::Png16
+        )
+    }
+
+    /// The MIME type of files in this format.
+    pub const fn media_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Ppm | ImageFormat::PpmBinary => "image/x-portable-pixmap",
+            ImageFormat::Png8 | ImageFormat::Png16 => "image/png",
+            ImageFormat::Exr(_) => "image/x-exr",
+            ImageFormat::Hdr => "image/vnd.radiance",
+            ImageFormat::Pfm => "image/x-portable-floatmap",
+        }
+    }
+
+    /// Whether the format can store the aov buffers of a film.
+    pub const fn supports_aovs(&self) -> bool {
+        matches!(self, ImageFormat::Exr(_))
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ppm" | "p3" => Ok(Self::Ppm),
+            "p6" => Ok(Self::PpmBinary),
+            "png" | "png8" => Ok(Self::Png8),
+            "png16" => Ok(Self::Png16),
+            exr if exr.starts_with("exr") => Ok(Self::Exr(s.parse()?)),
+            "hdr" | "rgbe" => Ok(Self::Hdr),
+            "pfm" => Ok(Self::Pfm),
+            other => Err(format!(
+                "unknown image format '{}', expected one of ppm, p6, png8, png16, exr[:options], hdr, pfm",
+                other
+            )),
+        }
+    }
+}
+
+impl Display for ImageFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageFormat::Ppm => write!(f, "ppm"),
+            ImageFormat::PpmBinary => write!(f, "p6"),
+            ImageFormat::Png8 => write!(f, "png8"),
+            ImageFormat::Png16 => write!(f, "png16"),
+            ImageFormat::Exr(settings) => write!(f, "{}", settings),
+            ImageFormat::Hdr => write!(f, "hdr"),
+            ImageFormat::Pfm => write!(f, "pfm"),
+        }
+    }
+}
+
+/// Where and how a finished [`Film`] gets written, stdout when there is no path.
+///
+/// Display referred formats go through the [`PostProcess`] first, scene referred
+/// ones store the film untouched.
+#[derive(Clone, Debug)]
+pub struct Output {
+    path: Option<PathBuf>,
+    format: ImageFormat,
+    post_process: PostProcess,
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Self::stdout(ImageFormat::Ppm)
+    }
+}
+
+impl Output {
+    pub const fn stdout(format: ImageFormat) -> Self {
+        Self {
+            path: None,
+            format,
+            post_process: PostProcess::DEFAULT,
+        }
+    }
+
+    pub fn new(path: impl Into<PathBuf>, format: ImageFormat) -> Self {
+        Self {
+            path: Some(path.into()),
+            format,
+            post_process: PostProcess::DEFAULT,
+        }
+    }
+
+    /// Writes to `path` in the format implied by its extension.
+    pub fn file(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        let format = ImageFormat::from_path(&path).ok_or_else(|| {
+            format!(
+                "can not guess the image format of '{}', set it explicitly",
+                path.display()
+            )
+        })?;
+        Ok(Self::new(path, format))
+    }
+
+    pub const fn set_format(mut self, format: ImageFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub const fn set_post_process(mut self, post_process: PostProcess) -> Self {
+        self.post_process = post_process;
+        self
+    }
+
+    pub const fn post_process(&self) -> &PostProcess {
+        &self.post_process
+    }
+
+    pub const fn format(&self) -> ImageFormat {
+        self.format
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// The same output with `_suffix` added to the file name, before the extension.
+    pub fn with_suffix(&self, suffix: &str) -> Self {
+        let path = self.path.as_ref().map(|path| {
+            let mut name = path.file_stem().unwrap_or_default().to_os_string();
+            name.push(format!("_{}", suffix));
+            if let Some(extension) = path.extension() {
+                name.push(".");
+                name.push(extension);
+            }
+            path.with_file_name(name)
+        });
+        Self {
+            path,
+            ..self.clone()
+        }
+    }
+
+    pub fn write(&self, film: &Film) -> io::Result<()> {
+        match &self.path {
+            Some(path) => {
+                let mut out = BufWriter::new(File::create(path)?);
+                self.encode(film, &mut out)?;
+                out.flush()
+            }
+            None => {
+                let stdout = io::stdout();
+                let mut out = BufWriter::new(stdout.lock());
+                self.encode(film, &mut out)?;
+                out.flush()
+            }
+        }
+    }
+
+    /// Writes the image to `out` instead of the path of this output.
+    pub fn encode(&self, film: &Film, out: impl Write) -> io::Result<()> {
+        match self.format.is_display_referred() {
+            true => self.format.write(&self.post_process.apply(film), out),
+            false => self.format.write(film, out),
+        }
+    }
+}
+
+fn write_ppm(film: &Film, mut out: impl Write) -> io::Result<()> {
+    write!(out, "P3\n{} {}\n255\n", film.width(), film.height())?;
+    for pixel in film.pixels() {
+        writeln!(out, "{}", Color::clamped(*pixel))?;
+    }
+    Ok(())
+}
+
+fn write_ppm_binary(film: &Film, mut out: impl Write) -> io::Result<()> {
+    write!(out, "P6\n{} {}\n255\n", film.width(), film.height())?;
+    for pixel in film.pixels() {
+        out.write_all(&Color::clamped(*pixel).to_srgb8())?;
+    }
+    Ok(())
+}
+
+fn write_png(film: &Film, out: impl Write, depth: png::BitDepth) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(out, film.width() as u32, film.height() as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(depth);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    let data: Vec<u8> = match depth {
+        png::BitDepth::Sixteen => film
+            .pixels()
+            .iter()
+            .flat_map(|p| Color::clamped(*p).to_srgb16())
+            .flat_map(u16::to_be_bytes)
+            .collect(),
+        _ => film
+            .pixels()
+            .iter()
+            .flat_map(|p| Color::clamped(*p).to_srgb8())
+            .collect(),
+    };
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&data)?;
+    writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,182 @@
+use std::{
+    fmt::Display,
+    io::{self, Cursor, Write},
+    str::FromStr,
+};
+
+use exr::prelude::{
+    AnyChannel, AnyChannels, Blocks, Compression, Encoding, FlatSamples, Image, Layer,
+    LayerAttributes, LineOrder, SmallVec, Vec2, WritableImage,
+};
+
+use crate::{film::Film, vector::Vec3};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExrPrecision {
+    Half,
+    Float,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExrCompression {
+    None,
+    Rle,
+    Zip,
+    Piz,
+}
+
+/// How the pixels of an OpenEXR file are laid out and compressed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExrSettings {
+    pub precision: ExrPrecision,
+    pub compression: ExrCompression,
+    /// Tile size in pixels, scanlines when `None`.
+    pub tile_size: Option<usize>,
+}
+
+impl Default for ExrSettings {
+    fn default() -> Self {
+        Self {
+            precision: ExrPrecision::Half,
+            compression: ExrCompression::Zip,
+            tile_size: None,
+        }
+    }
+}
+
+impl ExrSettings {
+    const DEFAULT_TILE_SIZE: usize = 64;
+
+    const fn encoding(&self) -> Encoding {
+        Encoding {
+            compression: match self.compression {
+                ExrCompression::None => Compression::Uncompressed,
+                ExrCompression::Rle => Compression::RLE,
+                ExrCompression::Zip => Compression::ZIP16,
+                ExrCompression::Piz => Compression::PIZ,
+            },
+            blocks: match self.tile_size {
+                Some(size) => Blocks::Tiles(Vec2(size, size)),
+                None => Blocks::ScanLines,
+            },
+            line_order: LineOrder::Increasing,
+        }
+    }
+
+    fn samples(&self, values: impl Iterator<Item = f64>) -> FlatSamples {
+        match self.precision {
+            ExrPrecision::Half => {
+                FlatSamples::F16(values.map(exr::prelude::f16::from_f64).collect())
+            }
+            ExrPrecision::Float => FlatSamples::F32(values.map(|v| v as f32).collect()),
+        }
+    }
+
+    fn vec3_channels(
+        &self,
+        channels: &mut SmallVec<[AnyChannel<FlatSamples>; 4]>,
+        layer: &str,
+        names: [&str; 3],
+        values: &[Vec3],
+    ) {
+        let components: [fn(&Vec3) -> f64; 3] = [|v| v.x, |v| v.y, |v| v.z];
+        for (name, component) in names.into_iter().zip(components) {
+            let name = match layer.is_empty() {
+                true => name.to_string(),
+                false => format!("{}.{}", layer, name),
+            };
+            let samples = self.samples(values.iter().map(component));
+            channels.push(AnyChannel::new(name.as_str(), samples));
+        }
+    }
+}
+
+/// Parses `exr` optionally followed by `:`-separated options, e.g. `exr:float:tiled=32:piz`.
+impl FromStr for ExrSettings {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut options = s.split(':');
+        if !options
+            .next()
+            .is_some_and(|f| f.eq_ignore_ascii_case("exr"))
+        {
+            return Err(format!("'{}' is not an exr format", s));
+        }
+        let mut settings = Self::default();
+        for option in options {
+            match option.to_ascii_lowercase().as_str() {
+                "half" => settings.precision = ExrPrecision::Half,
+                "float" => settings.precision = ExrPrecision::Float,
+                "none" => settings.compression = ExrCompression::None,
+                "rle" => settings.compression = ExrCompression::Rle,
+                "zip" => settings.compression = ExrCompression::Zip,
+                "piz" => settings.compression = ExrCompression::Piz,
+                "scanline" => settings.tile_size = None,
+                "tiled" => settings.tile_size = Some(Self::DEFAULT_TILE_SIZE),
+                other => match other.strip_prefix("tiled=").map(str::parse::<usize>) {
+                    Some(Ok(size)) if size > 0 => settings.tile_size = Some(size),
+                    _ => {
+                        return Err(format!(
+                            "unknown exr option '{}', expected half, float, none, rle, zip, piz, scanline, tiled or tiled=N",
+                            other
+                        ))
+                    }
+                },
+            }
+        }
+        Ok(settings)
+    }
+}
+
+impl Display for ExrSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let precision = match self.precision {
+            ExrPrecision::Half => "half",
+            ExrPrecision::Float => "float",
+        };
+        let compression = match self.compression {
+            ExrCompression::None => "none",
+            ExrCompression::Rle => "rle",
+            ExrCompression::Zip => "zip",
+            ExrCompression::Piz => "piz",
+        };
+        write!(f, "exr:{}:{}", precision, compression)?;
+        match self.tile_size {
+            Some(size) => write!(f, ":tiled={}", size),
+            None => write!(f, ":scanline"),
+        }
+    }
+}
+
+/// Writes the beauty pass as `R`, `G`, `B` and every aov of the film as its own
+/// named layer: `depth.Z`, `normal.{X,Y,Z}`, `albedo.{R,G,B}` and `objectId.id`.
+pub fn write_exr(film: &Film, settings: &ExrSettings, mut out: impl Write) -> io::Result<()> {
+    let mut channels = SmallVec::new();
+    settings.vec3_channels(&mut channels, "", ["R", "G", "B"], film.pixels());
+    if let Some(aovs) = film.aovs() {
+        channels.push(AnyChannel::new(
+            "depth.Z",
+            settings.samples(aovs.depth.iter().copied()),
+        ));
+        settings.vec3_channels(&mut channels, "normal", ["X", "Y", "Z"], &aovs.normal);
+        settings.vec3_channels(&mut channels, "albedo", ["R", "G", "B"], &aovs.albedo);
+        channels.push(AnyChannel::new(
+            "objectId.id",
+            FlatSamples::U32(aovs.object_id.clone()),
+        ));
+    }
+    let layer = Layer::new(
+        (film.width(), film.height()),
+        LayerAttributes::named("beauty"),
+        settings.encoding(),
+        AnyChannels::sort(channels),
+    );
+    // exr needs to seek while writing so the file is assembled in memory first
+    let mut buffer = Cursor::new(Vec::new());
+    Image::from_layer(layer)
+        .write()
+        .to_buffered(&mut buffer)
+        .map_err(io::Error::other)?;
+    out.write_all(buffer.get_ref())
+}
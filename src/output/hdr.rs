@@ -0,0 +1,186 @@
+use std::io::{self, Write};
+
+use crate::{film::Film, vector::Vec3};
+
+/// Scanlines outside this width can not be run length encoded.
+const RLE_WIDTH: std::ops::Range<usize> = 8..0x8000;
+const MIN_RUN: usize = 4;
+const MAX_RUN: usize = 127;
+
+/// Writes a Radiance `.hdr` file with new style run length encoded RGBE scanlines.
+pub fn write_hdr(film: &Film, mut out: impl Write) -> io::Result<()> {
+    write!(
+        out,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        film.height(),
+        film.width()
+    )?;
+    for row in film.rows() {
+        let rgbe: Vec<[u8; 4]> = row.iter().map(to_rgbe).collect();
+        if !RLE_WIDTH.contains(&film.width()) {
+            out.write_all(&rgbe.concat())?;
+            continue;
+        }
+        let width = film.width() as u16;
+        out.write_all(&[2, 2])?;
+        out.write_all(&width.to_be_bytes())?;
+        for channel in 0..4 {
+            let bytes: Vec<u8> = rgbe.iter().map(|p| p[channel]).collect();
+            write_rle_channel(&bytes, &mut out)?;
+        }
+    }
+    Ok(())
+}
+
+/// Shared exponent encoding, the mantissas keep 8 bits relative to the brightest channel.
+fn to_rgbe(color: &Vec3) -> [u8; 4] {
+    let [r, g, b] = [color.x, color.y, color.z].map(|c| c.max(0.0));
+    let brightest = r.max(g).max(b);
+    if brightest < 1e-32 {
+        return [0; 4];
+    }
+    let exponent = brightest.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2f64.powi(exponent);
+    let mantissa = |c: f64| (c * scale).min(255.0) as u8;
+    [
+        mantissa(r),
+        mantissa(g),
+        mantissa(b),
+        (exponent + 128).clamp(0, 255) as u8,
+    ]
+}
+
+fn write_rle_channel(bytes: &[u8], out: &mut impl Write) -> io::Result<()> {
+    let mut start = 0;
+    while start < bytes.len() {
+        // find the next run that is long enough to be worth encoding
+        let mut run_start = start;
+        let mut run_len = 0;
+        while run_start < bytes.len() {
+            run_len = bytes[run_start..]
+                .iter()
+                .take(MAX_RUN)
+                .take_while(|&&b| b == bytes[run_start])
+                .count();
+            if run_len >= MIN_RUN {
+                break;
+            }
+            run_start += run_len;
+        }
+        if run_len < MIN_RUN {
+            run_start = bytes.len();
+        }
+        for literal in bytes[start..run_start].chunks(MAX_RUN + 1) {
+            out.write_all(&[literal.len() as u8])?;
+            out.write_all(literal)?;
+        }
+        if run_start < bytes.len() {
+            out.write_all(&[128 + run_len as u8, bytes[run_start]])?;
+        }
+        start = run_start + run_len;
+    }
+    Ok(())
+}
+
+/// Writes a little endian portable float map, rows go from bottom to top.
+pub fn write_pfm(film: &Film, mut out: impl Write) -> io::Result<()> {
+    write!(out, "PF\n{} {}\n-1.0\n", film.width(), film.height())?;
+    let rows: Vec<&[Vec3]> = film.rows().collect();
+    for row in rows.into_iter().rev() {
+        for pixel in row {
+            for c in [pixel.x, pixel.y, pixel.z] {
+                out.write_all(&(c as f32).to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads back the RGBE pixels of a file written by `write_hdr`.
+    fn decode(bytes: &[u8], width: usize, height: usize) -> Vec<[u8; 4]> {
+        let header = format!("-Y {} +X {}\n", height, width);
+        let start = bytes
+            .windows(header.len())
+            .position(|w| w == header.as_bytes())
+            .expect("resolution line")
+            + header.len();
+        let mut input = bytes[start..].iter().copied();
+        let mut next = || input.next().expect("truncated scanline");
+        let mut pixels = Vec::new();
+        for _ in 0..height {
+            let mut row = vec![[0; 4]; width];
+            if RLE_WIDTH.contains(&width) {
+                assert_eq!([next(), next()], [2, 2]);
+                assert_eq!(u16::from_be_bytes([next(), next()]) as usize, width);
+                for channel in 0..4 {
+                    let mut x = 0;
+                    while x < width {
+                        let count = next() as usize;
+                        if count > 128 {
+                            let value = next();
+                            for pixel in &mut row[x..x + count - 128] {
+                                pixel[channel] = value;
+                            }
+                            x += count - 128;
+                        } else {
+                            assert!(count > 0, "empty literal");
+                            for pixel in &mut row[x..x + count] {
+                                pixel[channel] = next();
+                            }
+                            x += count;
+                        }
+                    }
+                    assert_eq!(x, width, "channel {} overruns the scanline", channel);
+                }
+            } else {
+                for pixel in &mut row {
+                    *pixel = [next(), next(), next(), next()];
+                }
+            }
+            pixels.extend(row);
+        }
+        assert!(input.next().is_none(), "trailing bytes");
+        pixels
+    }
+
+    fn round_trip(width: usize, height: usize, color: impl Fn(usize, usize) -> Vec3) {
+        let mut film = Film::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                *film.pixel_mut(x, y) = color(x, y);
+            }
+        }
+        let mut bytes = Vec::new();
+        write_hdr(&film, &mut bytes).unwrap();
+        let expected: Vec<[u8; 4]> = film.pixels().iter().map(to_rgbe).collect();
+        assert_eq!(decode(&bytes, width, height), expected);
+    }
+
+    #[test]
+    fn long_runs_and_literals_decode() {
+        // a flat stretch longer than one run, then noise longer than one literal
+        round_trip(400, 3, |x, y| {
+            if x < 300 {
+                Vec3::new(0.5, 0.25, 2.0)
+            } else {
+                Vec3::new((x * 7 % 13) as f64, y as f64 * 0.1, (x % 3) as f64)
+            }
+        });
+    }
+
+    #[test]
+    fn short_runs_stay_literal() {
+        round_trip(64, 2, |x, _| Vec3::new((x / 3) as f64, 1.0, 0.0));
+    }
+
+    #[test]
+    fn widths_outside_the_rle_range_are_flat() {
+        for width in [1, 7, 0x8000] {
+            round_trip(width, 2, |x, y| Vec3::new(1.0, (x % 5) as f64, y as f64));
+        }
+    }
+}
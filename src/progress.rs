@@ -0,0 +1,173 @@
+//! Watching and stopping renders while they run.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use indicatif::ProgressBar;
+
+/// How far along a render is, handed to a [`ProgressObserver`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    /// Finished units of work, rows or tiles depending on how the render is split up.
+    pub done: usize,
+    pub total: usize,
+    /// Camera samples traced so far, over all pixels.
+    pub samples: u64,
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    pub fn fraction(&self) -> f64 {
+        match self.total {
+            0 => 1.0,
+            total => self.done as f64 / total as f64,
+        }
+    }
+
+    /// Assumes the remaining work goes as fast as the work so far.
+    pub fn eta(&self) -> Option<Duration> {
+        match self.done {
+            0 => None,
+            done => Some(
+                self.elapsed
+                    .mul_f64((self.total.saturating_sub(done)) as f64 / done as f64),
+            ),
+        }
+    }
+
+    pub const fn is_complete(&self) -> bool {
+        self.done >= self.total
+    }
+}
+
+/// Gets told about a render as it happens, from the render threads.
+pub trait ProgressObserver: Sync {
+    fn started(&self, _progress: &Progress) {}
+    /// Called after every finished unit of work.
+    fn update(&self, progress: &Progress);
+    /// Called once at the end, also when the render was cancelled.
+    fn finished(&self, _progress: &Progress) {}
+}
+
+/// Ignores all progress.
+pub struct Silent;
+
+impl ProgressObserver for Silent {
+    fn update(&self, _progress: &Progress) {}
+}
+
+/// Draws an `indicatif` bar on stderr.
+pub struct ProgressBarObserver {
+    bar: ProgressBar,
+}
+
+impl ProgressBarObserver {
+    pub fn new() -> Self {
+        Self {
+            bar: ProgressBar::new(0),
+        }
+    }
+}
+
+impl Default for ProgressBarObserver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressObserver for ProgressBarObserver {
+    fn started(&self, progress: &Progress) {
+        self.bar.set_length(progress.total as u64);
+        self.bar.reset();
+    }
+    fn update(&self, progress: &Progress) {
+        self.bar.set_position(progress.done as u64);
+    }
+    fn finished(&self, progress: &Progress) {
+        match progress.is_complete() {
+            true => self.bar.finish(),
+            false => self.bar.abandon(),
+        }
+    }
+}
+
+/// Asks a running render to stop, cheap to clone and share between threads.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token that is also cancelled once `deadline` has passed. Calling
+    /// [`CancellationToken::cancel`] on either one cancels both, the deadline only
+    /// applies to the returned token.
+    pub fn with_deadline(&self, deadline: Instant) -> Self {
+        Self {
+            cancelled: self.cancelled.clone(),
+            deadline: Some(self.deadline.map_or(deadline, |d| d.min(deadline))),
+        }
+    }
+
+    /// Work that already started is finished, everything else is skipped.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed) || self.deadline.is_some_and(|d| Instant::now() >= d)
+    }
+}
+
+/// Counts finished work from many threads and reports it to an observer.
+pub(crate) struct Tracker<'a> {
+    observer: &'a dyn ProgressObserver,
+    start: Instant,
+    total: usize,
+    done: AtomicUsize,
+    samples: AtomicU64,
+}
+
+impl<'a> Tracker<'a> {
+    pub(crate) fn start(observer: &'a dyn ProgressObserver, total: usize) -> Self {
+        let tracker = Self {
+            observer,
+            start: Instant::now(),
+            total,
+            done: AtomicUsize::new(0),
+            samples: AtomicU64::new(0),
+        };
+        observer.started(&tracker.snapshot());
+        tracker
+    }
+
+    pub(crate) fn add(&self, samples: u64) {
+        self.samples.fetch_add(samples, Ordering::Relaxed);
+        self.done.fetch_add(1, Ordering::Relaxed);
+        self.observer.update(&self.snapshot());
+    }
+
+    pub(crate) fn finish(&self) -> Progress {
+        let progress = self.snapshot();
+        self.observer.finished(&progress);
+        progress
+    }
+
+    fn snapshot(&self) -> Progress {
+        Progress {
+            done: self.done.load(Ordering::Relaxed),
+            total: self.total,
+            samples: self.samples.load(Ordering::Relaxed),
+            elapsed: self.start.elapsed(),
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use std::cell::RefCell;
+
+use rand::{
+    distributions::{DistIter, Uniform},
+    rngs::SmallRng,
+    thread_rng, Rng, SeedableRng,
+};
+
+/// Uniform numbers in 0-1, the way the camera consumes them.
+pub type SampleRng = DistIter<Uniform<f64>, SmallRng, f64>;
+
+thread_local! {
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_rng(thread_rng()).expect("thread_rng can not fail"));
+}
+
+/// Restarts the random numbers of the current thread, materials draw from these.
+pub fn reseed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = SmallRng::seed_from_u64(seed));
+}
+
+pub fn with_rng<T>(f: impl FnOnce(&mut SmallRng) -> T) -> T {
+    RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
+pub fn sample_rng(seed: u64) -> SampleRng {
+    SmallRng::seed_from_u64(seed).sample_iter(Uniform::new(0.0, 1.0))
+}
+
+/// A seed for one piece of work that only depends on the render seed and `index`,
+/// so the result does not change with the order threads pick work up in.
+pub const fn derive_seed(seed: u64, index: u64) -> u64 {
+    // splitmix64
+    let mut z = seed.wrapping_add(index.wrapping_add(1).wrapping_mul(0x9E3779B97F4A7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+pub fn entropy_seed() -> u64 {
+    thread_rng().gen()
+}
@@ -1,5 +1,8 @@
+//! Rays.
+
 use crate::vector::{Point3D, Vec3};
 
+/// A half line from `origin` along `dir`, sent at time `tm` of the shutter interval.
 pub struct Ray {
     origin: Point3D,
     dir: Vec3,
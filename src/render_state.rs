@@ -0,0 +1,521 @@
+//! Sample sums that outlive a single call to render, so renders can be continued
+//! later and checkpointed to disk.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use crate::{
+    film::{AovSample, Aovs, Film},
+    hittable::Hit,
+    materials::Material,
+    ray::Ray,
+    tiles::Tile,
+    vector::Vec3,
+    world::SharedWorld,
+};
+
+const MAGIC: &[u8; 8] = b"RTCKPT02";
+
+/// Everything a render has gathered so far, tile by tile.
+///
+/// Every sample draws its random numbers from a stream derived from the seed, its
+/// pixel and its index, so the seed and the sample count of each tile are all the
+/// random number state there is. Continuing a render therefore gives exactly the
+/// image rendering it in one go would have.
+pub struct RenderState {
+    width: usize,
+    height: usize,
+    seed: u64,
+    aovs: bool,
+    /// See [`crate::Scene::fingerprint`], 0 when unknown.
+    scene: u64,
+    tiles: Vec<Mutex<TileState>>,
+}
+
+#[derive(Clone)]
+pub(crate) struct TileState {
+    pub(crate) tile: Tile,
+    /// Samples taken in every pixel of the tile, each with a weight of one.
+    pub(crate) samples: usize,
+    /// Radiance sums, row by row.
+    pub(crate) color: Vec<Vec3>,
+    /// Empty unless aovs are gathered.
+    pub(crate) aovs: Vec<AovAccumulator>,
+}
+
+impl RenderState {
+    pub fn new(width: usize, height: usize, seed: u64, aovs: bool, tiles: Vec<Tile>) -> Self {
+        let tiles = tiles
+            .into_iter()
+            .map(|tile| Mutex::new(TileState::empty(tile, 0, aovs)))
+            .collect();
+        Self {
+            width,
+            height,
+            seed,
+            aovs,
+            scene: 0,
+            tiles,
+        }
+    }
+
+    /// Records which scene the samples are of, so a checkpoint is not continued
+    /// with another one.
+    pub const fn set_scene(mut self, fingerprint: u64) -> Self {
+        self.scene = fingerprint;
+        self
+    }
+
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    pub const fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub const fn has_aovs(&self) -> bool {
+        self.aovs
+    }
+
+    pub const fn scene(&self) -> u64 {
+        self.scene
+    }
+
+    /// The samples per pixel every part of the image has reached.
+    pub fn samples_per_pixel(&self) -> usize {
+        self.tiles
+            .iter()
+            .map(|tile| lock(tile).samples)
+            .min()
+            .unwrap_or(0)
+    }
+
+    pub(crate) fn tiles(&self) -> &[Mutex<TileState>] {
+        &self.tiles
+    }
+
+    /// The average of the samples so far, pixels without any are black.
+    pub fn film(&self) -> Film {
+        let mut film = Film::new(self.width, self.height);
+        let mut aovs = vec![AovSample::BACKGROUND; self.width * self.height];
+        for tile in &self.tiles {
+            let tile = lock(tile);
+            if tile.samples == 0 {
+                continue;
+            }
+            let scale = 1.0 / tile.samples as f64;
+            for (i, (x, y)) in tile.tile.pixels().enumerate() {
+                *film.pixel_mut(x, y) = tile.color[i] * scale;
+                if let Some(aov) = tile.aovs.get(i) {
+                    aovs[y * self.width + x] = aov.finish();
+                }
+            }
+        }
+        if self.aovs {
+            let mut buffers = Aovs::default();
+            buffers.extend(aovs);
+            film.set_aovs(buffers);
+        }
+        film
+    }
+
+    /// Writes a checkpoint next to `path` first and then moves it over, so a crash
+    /// while saving leaves the previous checkpoint intact.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        let mut out = BufWriter::new(File::create(&partial)?);
+        self.write(&mut out)?;
+        out.into_inner()?.sync_all()?;
+        std::fs::rename(&partial, path)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Little endian: the header, then every tile with its sums.
+    pub fn write(&self, mut out: impl Write) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        write_u64(&mut out, self.width as u64)?;
+        write_u64(&mut out, self.height as u64)?;
+        write_u64(&mut out, self.seed)?;
+        out.write_all(&[self.aovs as u8])?;
+        write_u64(&mut out, self.scene)?;
+        write_u64(&mut out, self.tiles.len() as u64)?;
+        for tile in &self.tiles {
+            lock(tile).write(&mut out)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a checkpoint written by [`RenderState::write`], a truncated one is
+    /// invalid data too.
+    pub fn read(input: impl Read) -> io::Result<Self> {
+        Self::read_untruncated(input).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => invalid("checkpoint is truncated"),
+            _ => e,
+        })
+    }
+
+    fn read_untruncated(mut input: impl Read) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a render checkpoint"));
+        }
+        let width = read_usize(&mut input)?;
+        let height = read_usize(&mut input)?;
+        let seed = read_u64(&mut input)?;
+        let mut flag = [0];
+        input.read_exact(&mut flag)?;
+        let aovs = flag[0] != 0;
+        let scene = read_u64(&mut input)?;
+        let tile_count = read_usize(&mut input)?;
+        let mut tiles = Vec::new();
+        let pixels = width
+            .checked_mul(height)
+            .ok_or_else(|| invalid("checkpoint image is too large"))?;
+        // tiles lie within the image, so more area than the image means an overlap
+        let mut area = 0;
+        for _ in 0..tile_count {
+            let tile = TileState::read(&mut input, aovs, (width, height))?;
+            area += tile.tile.area();
+            if area > pixels {
+                return Err(invalid("checkpoint tiles overlap"));
+            }
+            tiles.push(Mutex::new(tile));
+        }
+        if area < pixels {
+            return Err(invalid("checkpoint tiles do not cover the image"));
+        }
+        // only now that the sums of every pixel have been read is a map of them cheap
+        let mut covered = vec![false; pixels];
+        for tile in &tiles {
+            for (x, y) in lock(tile).tile.pixels() {
+                if std::mem::replace(&mut covered[y * width + x], true) {
+                    return Err(invalid("checkpoint tiles overlap"));
+                }
+            }
+        }
+        Ok(Self {
+            width,
+            height,
+            seed,
+            aovs,
+            scene,
+            tiles,
+        })
+    }
+}
+
+impl TileState {
+    /// Sums of no samples at all, starting after the first `samples`.
+    pub(crate) fn empty(tile: Tile, samples: usize, aovs: bool) -> Self {
+        Self {
+            tile,
+            samples,
+            color: vec![Vec3::ZERO; tile.area()],
+            aovs: match aovs {
+                true => vec![AovAccumulator::default(); tile.area()],
+                false => Vec::new(),
+            },
+        }
+    }
+
+    /// Adds the sums of samples taken after the ones already in `self`.
+    pub(crate) fn merge(&mut self, later: TileState) {
+        for (sum, added) in self.color.iter_mut().zip(later.color) {
+            *sum += added;
+        }
+        for (aov, added) in self.aovs.iter_mut().zip(later.aovs) {
+            aov.merge(&added);
+        }
+        self.samples = later.samples;
+    }
+
+    pub(crate) fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        for v in [self.tile.x, self.tile.y, self.tile.width, self.tile.height] {
+            write_u64(out, v as u64)?;
+        }
+        write_u64(out, self.samples as u64)?;
+        for c in &self.color {
+            write_vec3(out, c)?;
+        }
+        for aov in &self.aovs {
+            write_u64(out, aov.samples as u64)?;
+            write_u64(out, aov.hits as u64)?;
+            write_f64(out, aov.depth)?;
+            write_vec3(out, &aov.normal)?;
+            write_vec3(out, &aov.albedo)?;
+            out.write_all(&aov.object_id.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Reads a tile written by [`TileState::write`], which has to lie within an
+    /// image of `width` by `height` pixels.
+    pub(crate) fn read(
+        input: &mut impl Read,
+        aovs: bool,
+        (width, height): (usize, usize),
+    ) -> io::Result<Self> {
+        let tile = Tile {
+            x: read_usize(input)?,
+            y: read_usize(input)?,
+            width: read_usize(input)?,
+            height: read_usize(input)?,
+        };
+        if !tile.fits_in(width, height) {
+            return Err(invalid("tile lies outside the image"));
+        }
+        let samples = read_usize(input)?;
+        let color = (0..tile.area())
+            .map(|_| read_vec3(input))
+            .collect::<io::Result<_>>()?;
+        let mut tile_aovs = Vec::new();
+        if aovs {
+            for _ in 0..tile.area() {
+                tile_aovs.push(AovAccumulator {
+                    samples: read_usize(input)?,
+                    hits: read_usize(input)?,
+                    depth: read_f64(input)?,
+                    normal: read_vec3(input)?,
+                    albedo: read_vec3(input)?,
+                    object_id: {
+                        let mut bytes = [0; 4];
+                        input.read_exact(&mut bytes)?;
+                        u32::from_le_bytes(bytes)
+                    },
+                });
+            }
+        }
+        Ok(Self {
+            tile,
+            samples,
+            color,
+            aovs: tile_aovs,
+        })
+    }
+}
+
+/// A panic on another render thread does not make the sums any less valid.
+pub(crate) fn lock(tile: &Mutex<TileState>) -> std::sync::MutexGuard<'_, TileState> {
+    tile.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Sums up the first hits of the samples of one pixel.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct AovAccumulator {
+    samples: usize,
+    hits: usize,
+    depth: f64,
+    normal: Vec3,
+    albedo: Vec3,
+    object_id: u32,
+}
+impl AovAccumulator {
+    /// Adds a sample whose ray `r` first hit `first_hit`, the shape's index and the hit.
+    pub(crate) fn add(&mut self, r: &Ray, first_hit: Option<&(usize, Hit)>, world: &SharedWorld) {
+        self.samples += 1;
+        match first_hit {
+            Some((index, hit)) => {
+                if self.hits == 0 {
+                    self.object_id = *index as u32 + 1;
+                }
+                self.hits += 1;
+                self.depth += hit.t * r.direction().length();
+                self.normal += hit.normal;
+                self.albedo += *hit.mat.albedo(hit).vec3();
+            }
+            None => self.albedo += world.environment.color(r),
+        }
+    }
+    /// Adds the samples of `later`, which were taken after the ones in `self`.
+    fn merge(&mut self, later: &AovAccumulator) {
+        if self.hits == 0 {
+            self.object_id = later.object_id;
+        }
+        self.samples += later.samples;
+        self.hits += later.hits;
+        self.depth += later.depth;
+        self.normal += later.normal;
+        self.albedo += later.albedo;
+    }
+    pub(crate) fn finish(&self) -> AovSample {
+        if self.hits == 0 {
+            return AovSample {
+                albedo: match self.samples {
+                    0 => Vec3::ZERO,
+                    n => self.albedo / n as f64,
+                },
+                ..AovSample::BACKGROUND
+            };
+        }
+        let normal = match self.normal.near_zero() {
+            true => Vec3::ZERO,
+            false => self.normal.unit_vector(),
+        };
+        AovSample {
+            depth: self.depth / self.hits as f64,
+            normal,
+            albedo: self.albedo / self.samples as f64,
+            object_id: self.object_id,
+        }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_u64(out: &mut impl Write, v: u64) -> io::Result<()> {
+    out.write_all(&v.to_le_bytes())
+}
+
+fn write_f64(out: &mut impl Write, v: f64) -> io::Result<()> {
+    out.write_all(&v.to_le_bytes())
+}
+
+fn write_vec3(out: &mut impl Write, v: &Vec3) -> io::Result<()> {
+    write_f64(out, v.x)?;
+    write_f64(out, v.y)?;
+    write_f64(out, v.z)
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_usize(input: &mut impl Read) -> io::Result<usize> {
+    usize::try_from(read_u64(input)?).map_err(|_| invalid("checkpoint value out of range"))
+}
+
+fn read_f64(input: &mut impl Read) -> io::Result<f64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
+
+fn read_vec3(input: &mut impl Read) -> io::Result<Vec3> {
+    Ok(Vec3::new(
+        read_f64(input)?,
+        read_f64(input)?,
+        read_f64(input)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint(state: &RenderState) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        state.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn assert_invalid(bytes: &[u8], message: &str) {
+        match RenderState::read(bytes) {
+            Ok(_) => panic!("read a broken checkpoint, expected '{}'", message),
+            Err(e) => {
+                assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+                assert_eq!(e.to_string(), message);
+            }
+        }
+    }
+
+    /// Half the tiles have samples, the rest none yet.
+    fn partially_rendered(aovs: bool) -> RenderState {
+        let tiles = crate::tiles::TileOrder::Scanline.tiles(13, 7, 4);
+        let state = RenderState::new(13, 7, 42, aovs, tiles).set_scene(0xfeed);
+        for (i, tile) in state.tiles().iter().enumerate().step_by(2) {
+            let mut tile = lock(tile);
+            tile.samples = i + 1;
+            for (j, sum) in tile.color.iter_mut().enumerate() {
+                *sum = Vec3::new(i as f64, j as f64, 0.5);
+            }
+            for (j, aov) in tile.aovs.iter_mut().enumerate() {
+                aov.samples = i + 1;
+                aov.hits = j % 2;
+                aov.depth = j as f64;
+                aov.normal = Vec3::new(0.0, 1.0, 0.0);
+                aov.object_id = i as u32;
+            }
+        }
+        state
+    }
+
+    #[test]
+    fn partial_renders_read_back_the_same() {
+        for aovs in [false, true] {
+            let state = partially_rendered(aovs);
+            let bytes = checkpoint(&state);
+            let read = RenderState::read(bytes.as_slice()).unwrap();
+            assert_eq!(
+                (read.width(), read.height(), read.seed(), read.scene()),
+                (13, 7, 42, 0xfeed)
+            );
+            assert_eq!(read.has_aovs(), aovs);
+            assert_eq!(read.samples_per_pixel(), 0);
+            assert_eq!(read.tiles().len(), state.tiles().len());
+            for (read, written) in read.tiles().iter().zip(state.tiles()) {
+                let (read, written) = (lock(read), lock(written));
+                assert_eq!(read.tile, written.tile);
+                assert_eq!(read.samples, written.samples);
+                assert_eq!(read.color, written.color);
+            }
+            assert_eq!(checkpoint(&read), bytes);
+        }
+    }
+
+    #[test]
+    fn overlapping_tiles_are_invalid() {
+        let tile = |x, width| Tile {
+            x,
+            y: 0,
+            width,
+            height: 2,
+        };
+        // more area than the image
+        let state = RenderState::new(4, 2, 0, false, vec![tile(0, 3), tile(1, 3)]);
+        assert_invalid(&checkpoint(&state), "checkpoint tiles overlap");
+        // as much area as the image, but one column twice and another not at all
+        let state = RenderState::new(4, 2, 0, false, vec![tile(0, 2), tile(1, 2)]);
+        assert_invalid(&checkpoint(&state), "checkpoint tiles overlap");
+        let state = RenderState::new(4, 2, 0, false, vec![tile(0, 2)]);
+        assert_invalid(
+            &checkpoint(&state),
+            "checkpoint tiles do not cover the image",
+        );
+    }
+
+    #[test]
+    fn huge_images_without_tiles_are_invalid() {
+        let state = RenderState::new(1 << 20, 1 << 20, 0, false, Vec::new());
+        assert_invalid(
+            &checkpoint(&state),
+            "checkpoint tiles do not cover the image",
+        );
+    }
+
+    #[test]
+    fn truncated_checkpoints_are_invalid() {
+        let bytes = checkpoint(&partially_rendered(true));
+        for length in [0, 5, 30, 49, 60, 49 + 40 + 100, bytes.len() - 1] {
+            assert_invalid(&bytes[..length], "checkpoint is truncated");
+        }
+    }
+}
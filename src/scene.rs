@@ -0,0 +1,324 @@
+//! Reading and writing scene files.
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    error::Error,
+    fmt::Display,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use toml::Spanned;
+
+use crate::{
+    camera::Camera,
+    materials::Materials,
+    shapes::{Quad, Shape, Sphere},
+    vector::{Point3D, Vec3},
+    world::{Environment, World},
+};
+
+/// A world together with the camera it should be seen through.
+pub struct Scene {
+    pub world: World,
+    pub camera: Camera,
+}
+
+thread_local! {
+    /// What files named in the scene being read on this thread are relative to,
+    /// `None` when it may not name any.
+    static FILE_BASE: RefCell<Option<PathBuf>> = const { RefCell::new(Some(PathBuf::new())) };
+}
+
+/// Where a file a scene names, like a lens prescription, is. Relative paths are
+/// made absolute so the scene can be passed on as [`Scene::to_toml`].
+pub(crate) fn scene_file(path: &Path) -> Result<PathBuf, String> {
+    FILE_BASE.with(|base| match &*base.borrow() {
+        Some(base) => {
+            std::path::absolute(base.join(path)).map_err(|e| format!("{}: {}", path.display(), e))
+        }
+        None => Err(format!(
+            "this scene may not read files, like '{}'",
+            path.display()
+        )),
+    })
+}
+
+impl Scene {
+    /// Reads a TOML scene description, see `scenes/` for examples. Files it names
+    /// are relative to the scene file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|e| SceneError {
+            path: Some(path.to_path_buf()),
+            location: None,
+            message: e.to_string(),
+        })?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        Self::parse(&source, Some(base)).map_err(|e| e.with_path(path))
+    }
+
+    /// Files the scene names are relative to the working directory.
+    pub fn from_toml(source: &str) -> Result<Self, SceneError> {
+        Self::parse(source, Some(Path::new("")))
+    }
+
+    /// Like [`Scene::from_toml`], but scenes that name files are refused, for
+    /// scenes that come from somewhere untrusted.
+    pub fn from_toml_without_files(source: &str) -> Result<Self, SceneError> {
+        Self::parse(source, None)
+    }
+
+    fn parse(source: &str, file_base: Option<&Path>) -> Result<Self, SceneError> {
+        let previous = FILE_BASE.with(|base| base.replace(file_base.map(Path::to_path_buf)));
+        let file = toml::from_str::<SceneFile>(source);
+        FILE_BASE.with(|base| base.replace(previous));
+        let file = file.map_err(|e| SceneError::at(source, e.span(), e.message().to_string()))?;
+        file.build(source)
+    }
+
+    /// Writes the scene in the same format [`Scene::load`] reads.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_toml())
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(&SceneFile::from_scene(self))
+            .expect("scenes only contain values toml can represent")
+    }
+
+    /// A hash of [`Scene::to_toml`] leaving out the samples per pixel, which tells
+    /// whether samples taken of another scene can be added to this one's.
+    pub fn fingerprint(&self) -> u64 {
+        let mut file = SceneFile::from_scene(self);
+        file.camera = file.camera.set_samples_per_pixel(0);
+        let source = toml::to_string(&file).expect("scenes only contain values toml can represent");
+        // FNV-1a, which unlike the std hashers stays the same between releases
+        source.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+}
+
+/// What went wrong while loading a scene, with the line and column when known.
+#[derive(Debug)]
+pub struct SceneError {
+    path: Option<PathBuf>,
+    location: Option<(usize, usize)>,
+    message: String,
+}
+
+impl SceneError {
+    fn at(source: &str, span: Option<Range<usize>>, message: String) -> Self {
+        Self {
+            path: None,
+            location: span.map(|span| line_and_column(source, span.start)),
+            message,
+        }
+    }
+
+    fn with_path(mut self, path: &Path) -> Self {
+        self.path = Some(path.to_path_buf());
+        self
+    }
+
+    pub const fn location(&self) -> Option<(usize, usize)> {
+        self.location
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for SceneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}", path.display())?,
+            None => write!(f, "<scene>")?,
+        }
+        if let Some((line, column)) = self.location {
+            write!(f, ":{}:{}", line, column)?;
+        }
+        write!(f, ": {}", self.message.trim_end())
+    }
+}
+
+impl Error for SceneError {}
+
+/// One based line and column of a byte offset.
+fn line_and_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+    (line, column)
+}
+
+/// The on disk layout: materials are named once in `[materials]` and shapes refer
+/// to them by name.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    #[serde(default)]
+    camera: Camera,
+    #[serde(default)]
+    environment: Environment,
+    #[serde(default)]
+    materials: BTreeMap<String, Materials>,
+    #[serde(default)]
+    shapes: Vec<Spanned<ShapeDescription>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ShapeDescription {
+    Sphere {
+        center: Point3D,
+        /// Where a moving sphere is at time 1, the end of the default shutter interval.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        center_end: Option<Point3D>,
+        radius: f64,
+        material: String,
+    },
+    /// A parallelogram with corner `q` and edges `u` and `v`.
+    Quad {
+        q: Point3D,
+        u: Vec3,
+        v: Vec3,
+        material: String,
+    },
+}
+
+impl SceneFile {
+    fn build(self, source: &str) -> Result<Scene, SceneError> {
+        let mut world = World::new();
+        for shape in self.shapes {
+            let span = shape.span();
+            let material = |name: &str| {
+                self.materials.get(name).cloned().ok_or_else(|| {
+                    SceneError::at(
+                        source,
+                        Some(span.clone()),
+                        format!("there is no material named '{}'", name),
+                    )
+                })
+            };
+            match shape.into_inner() {
+                ShapeDescription::Sphere {
+                    center,
+                    center_end,
+                    radius,
+                    material: name,
+                } => {
+                    let mat = material(&name)?;
+                    let sphere = match center_end {
+                        Some(center_end) => Sphere::new_moving(center, center_end, radius, mat),
+                        None => Sphere::new(center, radius, mat),
+                    };
+                    world.add_shape(sphere);
+                }
+                ShapeDescription::Quad {
+                    q,
+                    u,
+                    v,
+                    material: name,
+                } => world.add_shape(Quad::new(q, u, v, material(&name)?)),
+            }
+        }
+        world.set_environment(self.environment);
+        Ok(Scene {
+            world,
+            camera: self.camera,
+        })
+    }
+
+    /// Gives every distinct material a name, in the order they are first used.
+    fn from_scene(scene: &Scene) -> Self {
+        let mut named: Vec<Materials> = Vec::new();
+        let mut name_of = |material: &Materials| {
+            let index = match named.iter().position(|m| m == material) {
+                Some(index) => index,
+                None => {
+                    named.push(material.clone());
+                    named.len() - 1
+                }
+            };
+            format!("material_{}", index)
+        };
+        let shapes = scene
+            .world
+            .shapes()
+            .iter()
+            .map(|shape| match shape {
+                Shape::Sphere(sphere) => ShapeDescription::Sphere {
+                    center: sphere.center(0.0),
+                    center_end: sphere.center_end(),
+                    radius: sphere.radius(),
+                    material: name_of(sphere.material()),
+                },
+                Shape::Quad(quad) => ShapeDescription::Quad {
+                    q: quad.corner(),
+                    u: quad.edges().0,
+                    v: quad.edges().1,
+                    material: name_of(quad.material()),
+                },
+            })
+            .map(|shape| Spanned::new(0..0, shape))
+            .collect();
+        Self {
+            camera: scene.camera.clone(),
+            environment: scene.world.environment().clone(),
+            materials: named
+                .into_iter()
+                .enumerate()
+                .map(|(i, m)| (format!("material_{}", i), m))
+                .collect(),
+            shapes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gallery;
+
+    #[test]
+    fn gallery_scenes_survive_saving_and_loading() {
+        for builtin in gallery::SCENES {
+            let saved = (builtin.build)(Some(7)).to_toml();
+            let loaded = Scene::from_toml(&saved)
+                .unwrap_or_else(|e| panic!("{} does not load again: {}", builtin.name, e));
+            assert_eq!(loaded.to_toml(), saved, "{} changed", builtin.name);
+        }
+    }
+
+    fn material_error(material: &str) -> SceneError {
+        let source = format!("[materials]\nbad = {}\n", material);
+        match Scene::from_toml(&source) {
+            Ok(_) => panic!("{} was accepted", material),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn out_of_range_materials_are_located_errors() {
+        let error = material_error(r#"{ type = "dielectric", refraction_index = 0.0 }"#);
+        assert_eq!(error.location(), Some((2, 7)));
+        assert!(error.message().contains("refraction_index"), "{}", error);
+        let error = material_error(r#"{ type = "metal", albedo = [0.5, 0.5, 0.5], fuzz = 1.5 }"#);
+        assert_eq!(error.location(), Some((2, 7)));
+        assert!(error.message().contains("fuzz"), "{}", error);
+    }
+
+    #[test]
+    fn metal_fuzz_defaults_to_zero() {
+        let source = "[materials]\nm = { type = \"metal\", albedo = [0.5, 0.5, 0.5] }\n\
+                      [[shapes]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = 1\nmaterial = \"m\"\n";
+        let saved = Scene::from_toml(source).unwrap().to_toml();
+        assert!(saved.contains("fuzz = 0.0"), "{}", saved);
+        assert!(saved.contains("albedo = [0.5, 0.5, 0.5]"), "{}", saved);
+    }
+}
@@ -0,0 +1,582 @@
+//! A small HTTP API that queues render jobs.
+//!
+//! | request                   | does                                                  |
+//! |---------------------------|-------------------------------------------------------|
+//! | `POST /jobs`              | queues the TOML scene in the body, or `?builtin=name` |
+//! | `GET /jobs`               | lists every job                                       |
+//! | `GET /jobs/{id}`          | status and progress of a job                          |
+//! | `GET /jobs/{id}/image`    | the image once the job is done                        |
+//! | `DELETE /jobs/{id}`       | cancels a job, a partly rendered image is kept        |
+//!
+//! `POST /jobs` also takes `format`, `spp`, `width`, `seed`, `exposure` and
+//! `tonemap` query parameters with the meaning of the command line options.
+//! Responses other than images are JSON. Jobs render one after another, each on
+//! all cores. Scenes may not name files, like lens prescriptions, so clients
+//! cannot read files of the server. Images may be at most 16384 pixels wide and
+//! high with 65536 samples per pixel, and at most 64 jobs may wait in the queue.
+//! Only the last 64 finished jobs are kept, older ones are forgotten together with
+//! their images.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Read, Take, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    gallery,
+    output::{ImageFormat, Output},
+    progress::{CancellationToken, Progress, ProgressObserver},
+    scene::Scene,
+    tonemap::{PostProcess, ToneMapper},
+};
+
+/// Requests with larger bodies are turned away.
+const MAX_BODY: usize = 16 << 20;
+/// Requests whose request line and headers together are larger are turned away.
+const MAX_HEAD: usize = 64 << 10;
+/// How long a connection may go without sending or taking any data.
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// Finished jobs kept around for their status and image, the oldest go first.
+const MAX_FINISHED: usize = 64;
+/// Jobs waiting to be rendered, more are turned away until some are done.
+const MAX_PENDING: usize = 64;
+/// The largest image width and height a job may ask for.
+const MAX_SIZE: usize = 16384;
+const MAX_SAMPLES_PER_PIXEL: usize = 65536;
+
+/// Accepts scenes over HTTP and renders them in the order they came in.
+pub struct Server {
+    listener: TcpListener,
+    jobs: Arc<Jobs>,
+}
+
+impl Server {
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(address)?,
+            jobs: Arc::default(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serves requests until accepting connections fails.
+    pub fn run(self) -> io::Result<()> {
+        let jobs = self.jobs.clone();
+        thread::spawn(move || jobs.render_forever());
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let jobs = self.jobs.clone();
+            thread::spawn(move || {
+                if let Err(e) = handle(stream, &jobs) {
+                    eprintln!("request failed: {}", e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Status {
+    Queued,
+    Rendering,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl Status {
+    const fn is_finished(&self) -> bool {
+        matches!(self, Status::Done | Status::Failed | Status::Cancelled)
+    }
+
+    const fn name(&self) -> &'static str {
+        match self {
+            Status::Queued => "queued",
+            Status::Rendering => "rendering",
+            Status::Done => "done",
+            Status::Failed => "failed",
+            Status::Cancelled => "cancelled",
+        }
+    }
+}
+
+struct Job {
+    status: Status,
+    /// Taken by the render thread when the job starts.
+    scene: Option<Scene>,
+    output: Output,
+    progress: Arc<JobProgress>,
+    cancel: CancellationToken,
+    image: Option<Vec<u8>>,
+    error: Option<String>,
+}
+
+#[derive(Default)]
+struct JobProgress(Mutex<Option<Progress>>);
+
+impl ProgressObserver for JobProgress {
+    fn update(&self, progress: &Progress) {
+        *lock(&self.0) = Some(*progress);
+    }
+    fn started(&self, progress: &Progress) {
+        self.update(progress);
+    }
+    fn finished(&self, progress: &Progress) {
+        self.update(progress);
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    next_id: u64,
+    jobs: BTreeMap<u64, Job>,
+    pending: VecDeque<u64>,
+}
+
+impl Queue {
+    /// Forgets the oldest finished jobs beyond [`MAX_FINISHED`].
+    fn prune(&mut self) {
+        let finished: Vec<u64> = self
+            .jobs
+            .iter()
+            .filter(|(_, job)| job.status.is_finished())
+            .map(|(id, _)| *id)
+            .collect();
+        for id in &finished[..finished.len().saturating_sub(MAX_FINISHED)] {
+            self.jobs.remove(id);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Jobs {
+    queue: Mutex<Queue>,
+    added: Condvar,
+}
+
+impl Jobs {
+    /// Queues a job, `None` when too many are waiting already.
+    fn add(&self, scene: Scene, output: Output) -> Option<u64> {
+        let mut queue = lock(&self.queue);
+        if queue.pending.len() >= MAX_PENDING {
+            return None;
+        }
+        queue.next_id += 1;
+        let id = queue.next_id;
+        queue.jobs.insert(
+            id,
+            Job {
+                status: Status::Queued,
+                scene: Some(scene),
+                output,
+                progress: Arc::default(),
+                cancel: CancellationToken::new(),
+                image: None,
+                error: None,
+            },
+        );
+        queue.pending.push_back(id);
+        self.added.notify_one();
+        Some(id)
+    }
+
+    fn render_forever(&self) {
+        loop {
+            let (id, scene, output, progress, cancel) = {
+                let mut queue = lock(&self.queue);
+                let id = loop {
+                    match queue.pending.pop_front() {
+                        Some(id) => break id,
+                        None => {
+                            queue = self
+                                .added
+                                .wait(queue)
+                                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        }
+                    }
+                };
+                let job = queue.jobs.get_mut(&id).expect("pending jobs exist");
+                job.status = Status::Rendering;
+                (
+                    id,
+                    job.scene.take().expect("pending jobs have a scene"),
+                    job.output.clone(),
+                    job.progress.clone(),
+                    job.cancel.clone(),
+                )
+            };
+            // a scene that makes the renderer panic only fails its own job
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                let film = scene.camera.render_with(&scene.world, &*progress, &cancel);
+                let mut image = Vec::new();
+                output.encode(&film, &mut image).map(|_| image)
+            }));
+            let mut queue = lock(&self.queue);
+            let job = queue
+                .jobs
+                .get_mut(&id)
+                .expect("only finished jobs are removed");
+            match result {
+                Ok(Ok(image)) => {
+                    job.image = Some(image);
+                    job.status = match cancel.is_cancelled() {
+                        true => Status::Cancelled,
+                        false => Status::Done,
+                    };
+                }
+                Ok(Err(e)) => {
+                    job.status = Status::Failed;
+                    job.error = Some(e.to_string());
+                }
+                Err(_) => {
+                    job.status = Status::Failed;
+                    job.error = Some("the renderer crashed on this scene".to_string());
+                }
+            }
+            queue.prune();
+        }
+    }
+
+    fn cancel(&self, id: u64) -> Option<String> {
+        let mut queue = lock(&self.queue);
+        queue.pending.retain(|pending| *pending != id);
+        let job = queue.jobs.get_mut(&id)?;
+        job.cancel.cancel();
+        if job.status == Status::Queued {
+            job.status = Status::Cancelled;
+            job.scene = None;
+        }
+        let json = job_json(id, job);
+        queue.prune();
+        Some(json)
+    }
+}
+
+fn job_json(id: u64, job: &Job) -> String {
+    let mut json = format!(
+        "{{\"id\":{},\"status\":\"{}\",\"format\":{}",
+        id,
+        job.status.name(),
+        json_string(&job.output.format().to_string())
+    );
+    if let Some(progress) = *lock(&job.progress.0) {
+        let _ = write!(
+            json,
+            ",\"progress\":{},\"samples\":{},\"elapsed_seconds\":{:.3}",
+            progress.fraction(),
+            progress.samples,
+            progress.elapsed.as_secs_f64()
+        );
+        if let (Some(eta), Status::Rendering) = (progress.eta(), job.status) {
+            let _ = write!(json, ",\"eta_seconds\":{:.3}", eta.as_secs_f64());
+        }
+    }
+    if let Some(error) = &job.error {
+        let _ = write!(json, ",\"error\":{}", json_string(error));
+    }
+    json.push('}');
+    json
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    const fn json(status: u16, body: String) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: body.into_bytes(),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self::json(status, format!("{{\"error\":{}}}", json_string(message)))
+    }
+}
+
+fn handle(stream: TcpStream, jobs: &Jobs) -> io::Result<()> {
+    // a client that stops halfway does not keep its thread forever
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut input = BufReader::new(stream.try_clone()?);
+    let response = match read_request(&mut input)? {
+        Ok(request) => route(&request, jobs),
+        Err(message) => Response::error(400, &message),
+    };
+    let reason = match response.status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    let mut out = io::BufWriter::new(stream);
+    write!(
+        out,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason,
+        response.content_type,
+        response.body.len()
+    )?;
+    out.write_all(&response.body)?;
+    out.flush()
+}
+
+/// The outer error is the connection failing, the inner one a malformed request.
+fn read_request(input: &mut impl BufRead) -> io::Result<Result<Request, String>> {
+    let mut head = input.take(MAX_HEAD as u64);
+    let mut line = String::new();
+    if !read_head_line(&mut head, &mut line)? {
+        return Ok(Err(format!("headers are limited to {} bytes", MAX_HEAD)));
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Ok(Err("malformed request line".to_string()));
+    };
+    let (method, target) = (method.to_string(), target.to_string());
+    let mut content_length = 0;
+    loop {
+        line.clear();
+        if !read_head_line(&mut head, &mut line)? {
+            return Ok(Err(format!("headers are limited to {} bytes", MAX_HEAD)));
+        }
+        if line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                match value.trim().parse() {
+                    Ok(length) => content_length = length,
+                    Err(_) => return Ok(Err("bad Content-Length".to_string())),
+                }
+            }
+        }
+    }
+    if content_length > MAX_BODY {
+        return Ok(Err(format!("bodies are limited to {} bytes", MAX_BODY)));
+    }
+    let mut body = vec![0; content_length];
+    head.into_inner().read_exact(&mut body)?;
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect();
+    Ok(Ok(Request {
+        method,
+        path: path.to_string(),
+        query,
+        body,
+    }))
+}
+
+/// Reads a line of the request line and headers, false when they go on past
+/// [`MAX_HEAD`]. The end of the connection counts as an empty line.
+fn read_head_line(head: &mut Take<impl BufRead>, line: &mut String) -> io::Result<bool> {
+    head.read_line(line)?;
+    Ok(line.ends_with('\n') || head.limit() > 0)
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (escaped, bytes[i]) {
+            (Some(byte), _) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            (None, b'+') => decoded.push(b' '),
+            (None, byte) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn route(request: &Request, jobs: &Jobs) -> Response {
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    match (request.method.as_str(), segments.as_slice()) {
+        ("POST", ["jobs"]) => match new_job(request) {
+            Ok((scene, output)) => match jobs.add(scene, output) {
+                Some(id) => {
+                    let queue = lock(&jobs.queue);
+                    Response::json(201, job_json(id, &queue.jobs[&id]))
+                }
+                None => Response::error(503, "too many jobs are queued, try again later"),
+            },
+            Err(message) => Response::error(400, &message),
+        },
+        ("GET", ["jobs"]) => {
+            let queue = lock(&jobs.queue);
+            let list: Vec<String> = queue
+                .jobs
+                .iter()
+                .map(|(id, job)| job_json(*id, job))
+                .collect();
+            Response::json(200, format!("[{}]", list.join(",")))
+        }
+        ("GET", ["jobs", id]) => {
+            let queue = lock(&jobs.queue);
+            match id
+                .parse()
+                .ok()
+                .and_then(|id| queue.jobs.get(&id).map(|j| (id, j)))
+            {
+                Some((id, job)) => Response::json(200, job_json(id, job)),
+                None => Response::error(404, "no such job"),
+            }
+        }
+        ("GET", ["jobs", id, "image"]) => {
+            let queue = lock(&jobs.queue);
+            match id.parse().ok().and_then(|id| queue.jobs.get(&id)) {
+                Some(Job {
+                    image: Some(image),
+                    output,
+                    ..
+                }) => Response {
+                    status: 200,
+                    content_type: output.format().media_type(),
+                    body: image.clone(),
+                },
+                Some(job) => Response::error(
+                    409,
+                    &format!("the job is {}, there is no image", job.status.name()),
+                ),
+                None => Response::error(404, "no such job"),
+            }
+        }
+        ("DELETE", ["jobs", id]) => match id.parse().ok().and_then(|id| jobs.cancel(id)) {
+            Some(json) => Response::json(200, json),
+            None => Response::error(404, "no such job"),
+        },
+        (_, ["jobs"]) | (_, ["jobs", _]) | (_, ["jobs", _, "image"]) => {
+            Response::error(405, "method not allowed")
+        }
+        _ => Response::error(404, "not found"),
+    }
+}
+
+/// Reads the scene and the render options of a `POST /jobs`.
+fn new_job(request: &Request) -> Result<(Scene, Output), String> {
+    let param = |name: &str| {
+        request
+            .query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    fn parsed<T: std::str::FromStr>(name: &str, value: Option<&str>) -> Result<Option<T>, String> {
+        value
+            .map(|v| v.parse().map_err(|_| format!("bad {} '{}'", name, v)))
+            .transpose()
+    }
+    let seed = parsed::<u64>("seed", param("seed"))?;
+    let mut scene = match param("builtin") {
+        Some(name) => gallery::build(name, seed)
+            .ok_or_else(|| format!("there is no built in scene named '{}'", name))?,
+        None => {
+            let source = std::str::from_utf8(&request.body)
+                .map_err(|_| "the scene is not UTF-8".to_string())?;
+            Scene::from_toml_without_files(source).map_err(|e| e.to_string())?
+        }
+    };
+    let format = match param("format") {
+        Some(format) => format.parse::<ImageFormat>()?,
+        None => ImageFormat::Png8,
+    };
+    let mut post_process = PostProcess::default();
+    if let Some(exposure) = parsed::<f64>("exposure", param("exposure"))? {
+        post_process = post_process.set_exposure(exposure);
+    }
+    if let Some(tone_mapper) = param("tonemap") {
+        post_process = post_process.set_tone_mapper(tone_mapper.parse::<ToneMapper>()?);
+    }
+    let mut camera = scene
+        .camera
+        .set_seed(seed)
+        .set_verbose(false)
+        .set_aovs(format.supports_aovs());
+    if let Some(spp) = parsed::<usize>("spp", param("spp"))? {
+        camera = camera.set_samples_per_pixel(spp);
+    }
+    if let Some(width) = parsed::<usize>("width", param("width"))? {
+        let aspect = camera.image_width() as f64 / camera.image_height() as f64;
+        camera = camera.set_image_width_with_aspect_ratio(width, aspect);
+    }
+    // scenes give their size and samples too, so check what the job ends up with
+    let (width, height) = (camera.image_width(), camera.image_height());
+    if width > MAX_SIZE || height > MAX_SIZE {
+        return Err(format!(
+            "the image is {}x{}, at most {}x{} pixels are allowed",
+            width, height, MAX_SIZE, MAX_SIZE
+        ));
+    }
+    if camera.samples_per_pixel() > MAX_SAMPLES_PER_PIXEL {
+        return Err(format!(
+            "{} samples per pixel asked for, at most {} are allowed",
+            camera.samples_per_pixel(),
+            MAX_SAMPLES_PER_PIXEL
+        ));
+    }
+    scene.camera = camera;
+    Ok((scene, Output::stdout(format).set_post_process(post_process)))
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -1,20 +1,34 @@
+//! The geometry a [`World`](crate::World) is made of.
+
+mod quad;
 mod sphere;
+pub use quad::Quad;
+use serde::{Deserialize, Serialize};
 pub use sphere::Sphere;
 
 pub use crate::hittable::*;
-#[derive(Clone, Debug)]
+/// Serialized as a table with a `type` key naming the shape.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum Shape {
     Sphere(Sphere),
+    Quad(Quad),
 }
 impl From<Sphere> for Shape {
     fn from(value: Sphere) -> Self {
         Self::Sphere(value)
     }
 }
+impl From<Quad> for Shape {
+    fn from(value: Quad) -> Self {
+        Self::Quad(value)
+    }
+}
 impl Hittable for Shape {
     fn hit(&self, r: &crate::ray::Ray) -> Option<Hit> {
         match self {
             Shape::Sphere(s) => s.hit(r),
+            Shape::Quad(q) => q.hit(r),
         }
     }
 }
@@ -0,0 +1,119 @@
+use crate::{
+    materials::Materials,
+    ray::Ray,
+    vector::{Point3D, Vec3},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::*;
+/// A parallelogram with corner `q` spanned by the edges `u` and `v`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "QuadSettings", into = "QuadSettings")]
+pub struct Quad {
+    q: Point3D,
+    u: Vec3,
+    v: Vec3,
+    mat: Materials,
+    normal: Vec3,
+    d: f64,
+    w: Vec3,
+}
+
+impl Quad {
+    pub fn new(q: Point3D, u: Vec3, v: Vec3, mat: Materials) -> Self {
+        let n = u.cross(&v);
+        let normal = n.unit_vector();
+        Self {
+            q,
+            u,
+            v,
+            mat,
+            d: normal.dot(&q),
+            w: n / n.dot(&n),
+            normal,
+        }
+    }
+    /// The six sides of the axis aligned box with opposite corners `a` and `b`.
+    pub fn cuboid(a: Point3D, b: Point3D, mat: Materials) -> Vec<Quad> {
+        let min = Point3D::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+        let max = Point3D::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+        let dx = Vec3::new(max.x - min.x, 0.0, 0.0);
+        let dy = Vec3::new(0.0, max.y - min.y, 0.0);
+        let dz = Vec3::new(0.0, 0.0, max.z - min.z);
+        vec![
+            // front, right, back, left, top and bottom
+            Quad::new(Point3D::new(min.x, min.y, max.z), dx, dy, mat.clone()),
+            Quad::new(Point3D::new(max.x, min.y, max.z), -dz, dy, mat.clone()),
+            Quad::new(Point3D::new(max.x, min.y, min.z), -dx, dy, mat.clone()),
+            Quad::new(Point3D::new(min.x, min.y, min.z), dz, dy, mat.clone()),
+            Quad::new(Point3D::new(min.x, max.y, max.z), dx, -dz, mat.clone()),
+            Quad::new(Point3D::new(min.x, min.y, min.z), dx, dz, mat),
+        ]
+    }
+    /// Turns the quad about the y axis through the origin, counter clockwise seen from above.
+    pub fn rotate_y(self, degrees: f64) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let rotate = |p: Vec3| Vec3::new(cos * p.x + sin * p.z, p.y, -sin * p.x + cos * p.z);
+        Self::new(rotate(self.q), rotate(self.u), rotate(self.v), self.mat)
+    }
+    pub fn translate(self, offset: Vec3) -> Self {
+        Self::new(self.q + offset, self.u, self.v, self.mat)
+    }
+    pub const fn corner(&self) -> Point3D {
+        self.q
+    }
+    pub const fn edges(&self) -> (Vec3, Vec3) {
+        (self.u, self.v)
+    }
+    pub const fn material(&self) -> &Materials {
+        &self.mat
+    }
+}
+
+/// The serialized form of a [`Quad`], without the derived plane.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QuadSettings {
+    q: Point3D,
+    u: Vec3,
+    v: Vec3,
+    material: Materials,
+}
+impl From<QuadSettings> for Quad {
+    fn from(value: QuadSettings) -> Self {
+        Quad::new(value.q, value.u, value.v, value.material)
+    }
+}
+impl From<Quad> for QuadSettings {
+    fn from(value: Quad) -> Self {
+        Self {
+            q: value.q,
+            u: value.u,
+            v: value.v,
+            material: value.mat,
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray) -> Option<Hit> {
+        let denominator = self.normal.dot(r.direction());
+        // parallel to the plane
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+        let t = (self.d - self.normal.dot(r.origin())) / denominator;
+        if t < 0.0000001 || t.is_nan() {
+            return None;
+        }
+        let p = r.at(t);
+        let planar = p - self.q;
+        let alpha = self.w.dot(&planar.cross(&self.v));
+        let beta = self.w.dot(&self.u.cross(&planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+        Some(Hit::new(r, p, self.normal, self.mat.clone(), t))
+    }
+}
@@ -4,8 +4,12 @@ use crate::{
     vector::{Point3D, Vec3},
 };
 
+use serde::{Deserialize, Serialize};
+
 use super::*;
-#[derive(Clone, Debug)]
+/// A sphere, optionally moving in a straight line while the shutter is open.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "SphereSettings", into = "SphereSettings")]
 pub struct Sphere {
     center_start: Point3D,
     radius: f64,
@@ -15,7 +19,7 @@ pub struct Sphere {
 }
 
 impl Sphere {
-    pub fn new(center: Point3D, radius: f64, mat: Materials) -> Self {
+    pub const fn new(center: Point3D, radius: f64, mat: Materials) -> Self {
         Self {
             center_start: center,
             radius,
@@ -38,8 +42,55 @@ impl Sphere {
             center_vec: center_end - center_start,
         }
     }
+    /// Where a moving sphere ends up at time 1, the end of the default shutter interval.
+    pub fn center_end(&self) -> Option<Point3D> {
+        match self.is_moving {
+            true => Some(self.center_start + self.center_vec),
+            false => None,
+        }
+    }
+    pub const fn radius(&self) -> f64 {
+        self.radius
+    }
+    pub const fn material(&self) -> &Materials {
+        &self.mat
+    }
     pub fn center(&self, time: f64) -> Point3D {
-        self.center_start + time * self.center_vec
+        match self.is_moving {
+            true => self.center_start + time * self.center_vec,
+            false => self.center_start,
+        }
+    }
+}
+
+/// The serialized form of a [`Sphere`], the motion vector is stored as an end point.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SphereSettings {
+    center: Point3D,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    center_end: Option<Point3D>,
+    radius: f64,
+    material: Materials,
+}
+impl From<SphereSettings> for Sphere {
+    fn from(value: SphereSettings) -> Self {
+        match value.center_end {
+            Some(center_end) => {
+                Sphere::new_moving(value.center, center_end, value.radius, value.material)
+            }
+            None => Sphere::new(value.center, value.radius, value.material),
+        }
+    }
+}
+impl From<Sphere> for SphereSettings {
+    fn from(value: Sphere) -> Self {
+        Self {
+            center: value.center_start,
+            center_end: value.center_end(),
+            radius: value.radius,
+            material: value.mat,
+        }
     }
 }
 
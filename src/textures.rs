@@ -0,0 +1,97 @@
+//! Textures for the color of diffuse materials.
+
+mod checker;
+mod noise;
+
+use std::fmt;
+
+use serde::{
+    de::{self, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{color::Color, vector::Point3D};
+pub use checker::Checker;
+pub use noise::Noise;
+
+/// Serialized as a plain color when solid, otherwise as a table with a `type` key.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Textures {
+    Solid(Color),
+    Checker(Checker),
+    Noise(Noise),
+}
+/// A color that varies over space.
+pub trait Texture: Into<Textures> {
+    fn value(&self, p: &Point3D) -> Color;
+}
+
+impl Texture for Textures {
+    fn value(&self, p: &Point3D) -> Color {
+        match self {
+            Textures::Solid(c) => *c,
+            Textures::Checker(c) => c.value(p),
+            Textures::Noise(n) => n.value(p),
+        }
+    }
+}
+impl From<Color> for Textures {
+    fn from(value: Color) -> Self {
+        Textures::Solid(value)
+    }
+}
+impl From<Checker> for Textures {
+    fn from(value: Checker) -> Self {
+        Textures::Checker(value)
+    }
+}
+impl From<Noise> for Textures {
+    fn from(value: Noise) -> Self {
+        Textures::Noise(value)
+    }
+}
+
+/// The tagged form of the textures that are not a plain color.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Pattern {
+    Checker(Checker),
+    Noise(Noise),
+}
+
+impl Serialize for Textures {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Textures::Solid(c) => c.serialize(serializer),
+            Textures::Checker(c) => Pattern::Checker(c.clone()).serialize(serializer),
+            Textures::Noise(n) => Pattern::Noise(n.clone()).serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Textures {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TextureVisitor;
+        impl<'de> Visitor<'de> for TextureVisitor {
+            type Value = Textures;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a color or a texture table")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                Color::deserialize(de::value::SeqAccessDeserializer::new(seq)).map(Textures::Solid)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                Ok(
+                    match Pattern::deserialize(de::value::MapAccessDeserializer::new(map))? {
+                        Pattern::Checker(c) => Textures::Checker(c),
+                        Pattern::Noise(n) => Textures::Noise(n),
+                    },
+                )
+            }
+        }
+        deserializer.deserialize_any(TextureVisitor)
+    }
+}
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{color::Color, vector::Point3D};
+
+use super::Texture;
+/// Alternates between two colors in cubes of `scale` units through space.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Checker {
+    scale: f64,
+    even: Color,
+    odd: Color,
+}
+
+impl Checker {
+    pub const fn new(scale: f64, even: Color, odd: Color) -> Self {
+        Self { scale, even, odd }
+    }
+}
+impl Texture for Checker {
+    fn value(&self, p: &Point3D) -> Color {
+        let cell = |c: f64| (c / self.scale).floor() as i64;
+        match (cell(p.x) + cell(p.y) + cell(p.z)) % 2 == 0 {
+            true => self.even,
+            false => self.odd,
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::sync::{Arc, OnceLock};
+
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    color::Color,
+    vector::{Point3D, Vec3},
+};
+
+use super::Texture;
+/// Marble like stripes disturbed by Perlin turbulence.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "NoiseSettings", into = "NoiseSettings")]
+pub struct Noise {
+    scale: f64,
+    perlin: Arc<Perlin>,
+}
+
+impl Noise {
+    pub fn new(scale: f64) -> Self {
+        Self {
+            scale,
+            perlin: Perlin::shared(),
+        }
+    }
+}
+impl PartialEq for Noise {
+    fn eq(&self, other: &Self) -> bool {
+        self.scale == other.scale
+    }
+}
+impl Texture for Noise {
+    fn value(&self, p: &Point3D) -> Color {
+        let stripes = 1.0 + (self.scale * p.z + 10.0 * self.perlin.turbulence(p, 7)).sin();
+        let v = (0.5 * stripes).clamp(0.0, 1.0);
+        Color::new(Vec3::new(v, v, v))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoiseSettings {
+    scale: f64,
+}
+impl From<NoiseSettings> for Noise {
+    fn from(value: NoiseSettings) -> Self {
+        Noise::new(value.scale)
+    }
+}
+impl From<Noise> for NoiseSettings {
+    fn from(value: Noise) -> Self {
+        Self { scale: value.scale }
+    }
+}
+
+const POINT_COUNT: usize = 256;
+/// The lattice is the same in every run so noise textures render the same.
+const PERLIN_SEED: u64 = 0x5eed;
+
+/// Ken Perlin's gradient noise on a lattice of random unit vectors.
+#[derive(Debug)]
+struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    fn shared() -> Arc<Perlin> {
+        static PERLIN: OnceLock<Arc<Perlin>> = OnceLock::new();
+        PERLIN.get_or_init(|| Arc::new(Perlin::new())).clone()
+    }
+
+    fn new() -> Self {
+        let mut rng = SmallRng::seed_from_u64(PERLIN_SEED);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| {
+                Vec3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                )
+                .unit_vector()
+            })
+            .collect();
+        let mut permutation = || {
+            let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+            p.shuffle(&mut rng);
+            p
+        };
+        Self {
+            perm_x: permutation(),
+            perm_y: permutation(),
+            perm_z: permutation(),
+            gradients,
+        }
+    }
+
+    fn noise(&self, p: &Point3D) -> f64 {
+        let (u, v, w) = (p.x - p.x.floor(), p.y - p.y.floor(), p.z - p.z.floor());
+        let (i, j, k) = (p.x.floor() as i64, p.y.floor() as i64, p.z.floor() as i64);
+        let wrap = |n: i64| (n & (POINT_COUNT as i64 - 1)) as usize;
+
+        // hermite smoothing of the interpolation weights
+        let (uu, vv, ww) = (
+            u * u * (3.0 - 2.0 * u),
+            v * v * (3.0 - 2.0 * v),
+            w * w * (3.0 - 2.0 * w),
+        );
+        let mut accum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let gradient = &self.gradients[self.perm_x[wrap(i + di)]
+                        ^ self.perm_y[wrap(j + dj)]
+                        ^ self.perm_z[wrap(k + dk)]];
+                    let (fi, fj, fk) = (di as f64, dj as f64, dk as f64);
+                    let weight = Vec3::new(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * gradient.dot(&weight);
+                }
+            }
+        }
+        accum
+    }
+
+    fn turbulence(&self, p: &Point3D, depth: usize) -> f64 {
+        let mut accum = 0.0;
+        let mut p = *p;
+        let mut weight = 1.0;
+        for _ in 0..depth {
+            accum += weight * self.noise(&p);
+            weight *= 0.5;
+            p = p * 2.0;
+        }
+        accum.abs()
+    }
+}
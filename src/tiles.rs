@@ -0,0 +1,175 @@
+//! Splitting the image into tiles and choosing the order they are rendered in.
+
+use std::{fmt::Display, str::FromStr};
+
+/// A rectangle of pixels, `x` and `y` being its top left corner.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Tile {
+    pub const fn area(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// Whether the tile lies within an image of `width` by `height` pixels.
+    pub const fn fits_in(&self, width: usize, height: usize) -> bool {
+        match (
+            self.x.checked_add(self.width),
+            self.y.checked_add(self.height),
+        ) {
+            (Some(right), Some(bottom)) => right <= width && bottom <= height,
+            _ => false,
+        }
+    }
+
+    /// The pixel coordinates of the tile, row by row.
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (self.y..self.y + self.height)
+            .flat_map(move |y| (self.x..self.x + self.width).map(move |x| (x, y)))
+    }
+}
+
+/// The order tiles are handed out to the render threads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TileOrder {
+    /// Left to right, top to bottom.
+    #[default]
+    Scanline,
+    /// Outwards from the center of the image, where the subject usually is.
+    Spiral,
+    /// Along a Hilbert curve, neighbouring tiles are rendered close together in time.
+    Hilbert,
+}
+
+impl TileOrder {
+    /// Covers a `width` by `height` image with tiles of at most `size` pixels square.
+    pub fn tiles(&self, width: usize, height: usize, size: usize) -> Vec<Tile> {
+        let size = size.max(1);
+        let (columns, rows) = (width.div_ceil(size), height.div_ceil(size));
+        let mut grid: Vec<(usize, usize)> = (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (column, row)))
+            .collect();
+        match self {
+            TileOrder::Scanline => {}
+            TileOrder::Spiral => {
+                let center = (columns as f64 / 2.0 - 0.5, rows as f64 / 2.0 - 0.5);
+                // rings of tiles around the center, each walked around clockwise
+                grid.sort_by(|a, b| {
+                    spiral_key(*a, center)
+                        .partial_cmp(&spiral_key(*b, center))
+                        .expect("tile positions are finite")
+                });
+            }
+            TileOrder::Hilbert => {
+                let side = columns.max(rows).next_power_of_two();
+                grid.sort_by_key(|&(x, y)| hilbert_index(side, x, y));
+            }
+        }
+        grid.into_iter()
+            .map(|(column, row)| {
+                let (x, y) = (column * size, row * size);
+                Tile {
+                    x,
+                    y,
+                    width: size.min(width - x),
+                    height: size.min(height - y),
+                }
+            })
+            .collect()
+    }
+}
+
+fn spiral_key((x, y): (usize, usize), center: (f64, f64)) -> (f64, f64) {
+    let dx = x as f64 - center.0;
+    let dy = y as f64 - center.1;
+    (dx.abs().max(dy.abs()), dy.atan2(dx))
+}
+
+/// Distance along the Hilbert curve filling a `side` by `side` grid, `side` being a
+/// power of two.
+fn hilbert_index(side: usize, mut x: usize, mut y: usize) -> usize {
+    let mut index = 0;
+    let mut s = side / 2;
+    while s > 0 {
+        let rx = usize::from(x & s > 0);
+        let ry = usize::from(y & s > 0);
+        index += s * s * ((3 * rx) ^ ry);
+        // rotate the quadrant so the curve stays connected
+        if ry == 0 {
+            if rx == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    index
+}
+
+impl FromStr for TileOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "scanline" => Ok(Self::Scanline),
+            "spiral" => Ok(Self::Spiral),
+            "hilbert" => Ok(Self::Hilbert),
+            other => Err(format!(
+                "unknown tile order '{}', expected scanline, spiral or hilbert",
+                other
+            )),
+        }
+    }
+}
+
+impl Display for TileOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TileOrder::Scanline => write!(f, "scanline"),
+            TileOrder::Spiral => write!(f, "spiral"),
+            TileOrder::Hilbert => write!(f, "hilbert"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_order_covers_every_pixel_once() {
+        for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            for (width, height, size) in [
+                (100, 37, 16),
+                (7, 300, 32),
+                (33, 33, 32),
+                (1, 1, 8),
+                (250, 90, 1),
+            ] {
+                let tiles = order.tiles(width, height, size);
+                assert_eq!(tiles.len(), width.div_ceil(size) * height.div_ceil(size));
+                let mut covered = vec![0; width * height];
+                for tile in &tiles {
+                    assert!(tile.fits_in(width, height), "{:?} is outside", tile);
+                    for (x, y) in tile.pixels() {
+                        covered[y * width + x] += 1;
+                    }
+                }
+                assert!(
+                    covered.iter().all(|count| *count == 1),
+                    "{} does not cover {}x{} in tiles of {} exactly once",
+                    order,
+                    width,
+                    height,
+                    size
+                );
+            }
+        }
+    }
+}
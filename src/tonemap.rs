@@ -0,0 +1,264 @@
+//! Turning scene radiance into display values.
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::{film::Film, vector::Vec3};
+
+/// Rec. 709 luminance weights of linear sRGB.
+const LUMINANCE: Vec3 = Vec3::new(0.2126, 0.7152, 0.0722);
+/// Temperature of the sRGB white point, white balancing to it changes nothing.
+pub const NEUTRAL_TEMPERATURE: f64 = 6504.0;
+
+/// Curves that squeeze scene radiance into the displayable 0-1 range.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapper {
+    /// Cuts everything above 1, this is what the renderer always did.
+    Clamp,
+    /// Extended Reinhard on luminance, `white` is the radiance that maps to 1.
+    Reinhard { white: f64 },
+    /// John Hable's filmic curve from Uncharted 2.
+    Hable,
+    /// Stephen Hill's fit of the ACES reference rendering and sRGB output transforms.
+    Aces,
+    /// Troy Sobotka's AgX base transform.
+    Agx,
+}
+
+impl ToneMapper {
+    pub fn map(&self, c: Vec3) -> Vec3 {
+        match self {
+            ToneMapper::Clamp => c,
+            ToneMapper::Reinhard { white } => reinhard(c, *white),
+            ToneMapper::Hable => hable(c),
+            ToneMapper::Aces => aces(c),
+            ToneMapper::Agx => agx(c),
+        }
+    }
+}
+
+impl FromStr for ToneMapper {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_ascii_lowercase();
+        match s.as_str() {
+            "clamp" => Ok(Self::Clamp),
+            "reinhard" => Ok(Self::Reinhard { white: 4.0 }),
+            "hable" | "uncharted" => Ok(Self::Hable),
+            "aces" => Ok(Self::Aces),
+            "agx" => Ok(Self::Agx),
+            other => match other.strip_prefix("reinhard=").map(str::parse::<f64>) {
+                Some(Ok(white)) if white > 0.0 => Ok(Self::Reinhard { white }),
+                _ => Err(format!(
+                    "unknown tone mapper '{}', expected clamp, reinhard[=white], hable, aces or agx",
+                    other
+                )),
+            },
+        }
+    }
+}
+
+impl Display for ToneMapper {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ToneMapper::Clamp => write!(f, "clamp"),
+            ToneMapper::Reinhard { white } => write!(f, "reinhard={}", white),
+            ToneMapper::Hable => write!(f, "hable"),
+            ToneMapper::Aces => write!(f, "aces"),
+            ToneMapper::Agx => write!(f, "agx"),
+        }
+    }
+}
+
+/// Exposure, white balance and tone mapping, applied to a finished film before it
+/// gets encoded for display.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PostProcess {
+    exposure: f64,
+    white_balance: Vec3,
+    tone_mapper: ToneMapper,
+}
+
+impl Default for PostProcess {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl PostProcess {
+    /// Leaves the film as is apart from clamping it.
+    pub const DEFAULT: Self = Self {
+        exposure: 0.0,
+        white_balance: Vec3::new(1.0, 1.0, 1.0),
+        tone_mapper: ToneMapper::Clamp,
+    };
+
+    /// Exposure compensation in stops, every +1 doubles the brightness.
+    pub const fn set_exposure(mut self, exposure: f64) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    /// Neutralizes light of the given color temperature in kelvin, so 3200 makes a
+    /// tungsten lit scene look white.
+    pub fn set_white_balance(mut self, temperature: f64) -> Self {
+        self.white_balance = white_balance_gains(temperature);
+        self
+    }
+
+    /// Per channel multipliers, for when the gains are known already.
+    pub const fn set_white_balance_gains(mut self, gains: Vec3) -> Self {
+        self.white_balance = gains;
+        self
+    }
+
+    pub const fn set_tone_mapper(mut self, tone_mapper: ToneMapper) -> Self {
+        self.tone_mapper = tone_mapper;
+        self
+    }
+
+    pub const fn exposure(&self) -> f64 {
+        self.exposure
+    }
+
+    pub const fn white_balance(&self) -> &Vec3 {
+        &self.white_balance
+    }
+
+    pub const fn tone_mapper(&self) -> ToneMapper {
+        self.tone_mapper
+    }
+
+    pub fn apply_pixel(&self, c: &Vec3) -> Vec3 {
+        let c = (c * &self.white_balance) * 2f64.powf(self.exposure);
+        let c = Vec3::new(c.x.max(0.0), c.y.max(0.0), c.z.max(0.0));
+        self.tone_mapper.map(c)
+    }
+
+    /// A display referred copy of `film`, still linear so it can be sRGB encoded.
+    pub fn apply(&self, film: &Film) -> Film {
+        film.map(|c| self.apply_pixel(c))
+    }
+}
+
+fn luminance(c: &Vec3) -> f64 {
+    c.dot(&LUMINANCE)
+}
+
+fn mat3(m: [[f64; 3]; 3], c: Vec3) -> Vec3 {
+    let row = |r: [f64; 3]| r[0] * c.x + r[1] * c.y + r[2] * c.z;
+    Vec3::new(row(m[0]), row(m[1]), row(m[2]))
+}
+
+fn per_channel(c: Vec3, f: impl Fn(f64) -> f64) -> Vec3 {
+    Vec3::new(f(c.x), f(c.y), f(c.z))
+}
+
+fn reinhard(c: Vec3, white: f64) -> Vec3 {
+    let l = luminance(&c);
+    if l <= 0.0 {
+        return Vec3::ZERO;
+    }
+    let mapped = l * (1.0 + l / (white * white)) / (1.0 + l);
+    c * (mapped / l)
+}
+
+fn hable(c: Vec3) -> Vec3 {
+    const EXPOSURE_BIAS: f64 = 2.0;
+    const WHITE: f64 = 11.2;
+    fn partial(x: f64) -> f64 {
+        const A: f64 = 0.15;
+        const B: f64 = 0.50;
+        const C: f64 = 0.10;
+        const D: f64 = 0.20;
+        const E: f64 = 0.02;
+        const F: f64 = 0.30;
+        ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+    }
+    let white_scale = 1.0 / partial(WHITE);
+    per_channel(c, |x| partial(x * EXPOSURE_BIAS) * white_scale)
+}
+
+fn aces(c: Vec3) -> Vec3 {
+    const INPUT: [[f64; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    const OUTPUT: [[f64; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+    let rrt_and_odt = |v: f64| {
+        let a = v * (v + 0.0245786) - 0.000090537;
+        let b = v * (0.983729 * v + 0.4329510) + 0.238081;
+        a / b
+    };
+    let c = per_channel(mat3(INPUT, c), rrt_and_odt);
+    per_channel(mat3(OUTPUT, c), |v| v.clamp(0.0, 1.0))
+}
+
+fn agx(c: Vec3) -> Vec3 {
+    const INSET: [[f64; 3]; 3] = [
+        [0.842479062253094, 0.0784335999999992, 0.0792237451477643],
+        [0.0423282422610123, 0.878468636469772, 0.0791661274605434],
+        [0.0423756549057051, 0.0784336, 0.879142973793104],
+    ];
+    const OUTSET: [[f64; 3]; 3] = [
+        [1.19687900512017, -0.0980208811401368, -0.0990297440797205],
+        [-0.0528968517574562, 1.15190312990417, -0.0989611768448433],
+        [-0.0529716355144438, -0.0980434501171241, 1.15107367264116],
+    ];
+    const MIN_EV: f64 = -12.47393;
+    const MAX_EV: f64 = 4.026069;
+    // polynomial fit of the AgX base contrast sigmoid
+    let contrast = |x: f64| {
+        let x2 = x * x;
+        let x4 = x2 * x2;
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.00232
+    };
+    let c = per_channel(mat3(INSET, c), |v| {
+        let ev = v.max(1e-10).log2().clamp(MIN_EV, MAX_EV);
+        contrast((ev - MIN_EV) / (MAX_EV - MIN_EV))
+    });
+    // the sigmoid produces display encoded values, undo the 2.2 gamma it assumes
+    per_channel(mat3(OUTSET, c), |v| v.max(0.0).powf(2.2))
+}
+
+/// Channel gains that turn light of `temperature` kelvin neutral, keeping luminance.
+fn white_balance_gains(temperature: f64) -> Vec3 {
+    let reference = planckian_rgb(NEUTRAL_TEMPERATURE);
+    let light = planckian_rgb(temperature);
+    // very warm light has next to no blue left, keep the gain finite
+    let gains = Vec3::new(
+        reference.x / light.x.max(1e-3),
+        reference.y / light.y.max(1e-3),
+        reference.z / light.z.max(1e-3),
+    );
+    gains / luminance(&gains)
+}
+
+/// Linear sRGB color of a black body, from Kim et al.'s cubic fit of the Planckian locus.
+fn planckian_rgb(temperature: f64) -> Vec3 {
+    let t = temperature.clamp(1667.0, 25000.0);
+    let x = match t <= 4000.0 {
+        true => -0.2661239e9 / t.powi(3) - 0.2343589e6 / t.powi(2) + 0.8776956e3 / t + 0.179910,
+        false => -3.0258469e9 / t.powi(3) + 2.1070379e6 / t.powi(2) + 0.2226347e3 / t + 0.240390,
+    };
+    let y = if t <= 2222.0 {
+        -1.1063814 * x.powi(3) - 1.34811020 * x.powi(2) + 2.18555832 * x - 0.20219683
+    } else if t <= 4000.0 {
+        -0.9549476 * x.powi(3) - 1.37418593 * x.powi(2) + 2.09137015 * x - 0.16748867
+    } else {
+        3.0817580 * x.powi(3) - 5.87338670 * x.powi(2) + 3.75112997 * x - 0.37001483
+    };
+    const XYZ_TO_SRGB: [[f64; 3]; 3] = [
+        [3.2406, -1.5372, -0.4986],
+        [-0.9689, 1.8758, 0.0415],
+        [0.0557, -0.2040, 1.0570],
+    ];
+    let xyz = Vec3::new(x / y, 1.0, (1.0 - x - y) / y);
+    mat3(XYZ_TO_SRGB, xyz)
+}
@@ -1,12 +1,21 @@
+//! Three dimensional vectors.
+
 use std::{
     f64::consts::PI,
     iter::Sum,
     ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub},
 };
 
-use rand::{thread_rng, Rng};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::random;
 
-#[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
+/// Points, directions and linear radiance.
+///
+/// Serialized as a `[x, y, z]` array.
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug, Default, Serialize, Deserialize)]
+#[serde(from = "[f64; 3]", into = "[f64; 3]")]
 pub struct Vec3 {
     pub x: f64,
     pub y: f64,
@@ -54,15 +63,15 @@ impl Vec3 {
         self / self.length()
     }
 
-    pub fn z_mut(&mut self) -> &mut f64 {
+    pub const fn z_mut(&mut self) -> &mut f64 {
         &mut self.z
     }
 
-    pub fn y_mut(&mut self) -> &mut f64 {
+    pub const fn y_mut(&mut self) -> &mut f64 {
         &mut self.y
     }
 
-    pub fn x_mut(&mut self) -> &mut f64 {
+    pub const fn x_mut(&mut self) -> &mut f64 {
         &mut self.x
     }
 
@@ -71,7 +80,7 @@ impl Vec3 {
         const N: f64 = -S;
         (N..S).contains(&self.x) && (N..S).contains(&self.y) && (N..S).contains(&self.z)
     }
-    pub fn is_nan(&self) -> bool {
+    pub const fn is_nan(&self) -> bool {
         self.x.is_nan() || self.y.is_nan() || self.z.is_nan()
     }
     pub fn reflect(&self, normal: &Vec3) -> Vec3 {
@@ -86,6 +95,16 @@ impl Vec3 {
     }
 }
 
+impl From<[f64; 3]> for Vec3 {
+    fn from([x, y, z]: [f64; 3]) -> Self {
+        Self::new(x, y, z)
+    }
+}
+impl From<Vec3> for [f64; 3] {
+    fn from(value: Vec3) -> Self {
+        [value.x, value.y, value.z]
+    }
+}
 impl Neg for &Vec3 {
     type Output = Vec3;
 
@@ -227,10 +246,9 @@ impl Sum for Vec3 {
 }
 
 pub fn random_unit_in_disk() -> Vec3 {
-    let mut rng = thread_rng();
     // Generate random spherical coordinates
-    let phi = rng.gen_range(0.0..2.0 * PI);
-    let theta = rng.gen_range(0.0..PI / 2.0);
+    let (phi, theta) =
+        random::with_rng(|rng| (rng.gen_range(0.0..2.0 * PI), rng.gen_range(0.0..PI / 2.0)));
 
     // Convert spherical coordinates to Cartesian coordinates
     let x = theta.sin() * phi.cos();
@@ -241,5 +259,5 @@ pub fn random_unit_in_disk() -> Vec3 {
     Vec3::new(x, y, z)
 }
 pub fn random_f64_in_range() -> f64 {
-    thread_rng().gen_range(0.0..1.0)
+    random::with_rng(|rng| rng.gen_range(0.0..1.0))
 }
@@ -1,11 +1,72 @@
-use crate::{hittable::Hittable, shapes::Shape};
+//! The world and its environment.
 
+use serde::{Deserialize, Serialize};
+
+use crate::{hittable::Hittable, ray::Ray, shapes::Shape, vector::Vec3};
+
+/// The light arriving from rays that leave the scene without hitting anything.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Environment {
+    /// Blends from `bottom` straight down to `top` straight up.
+    Gradient {
+        bottom: Vec3,
+        top: Vec3,
+    },
+    Solid {
+        color: Vec3,
+    },
+}
+impl Environment {
+    /// The white to blue sky the renderer always had.
+    pub const SKY: Self = Self::Gradient {
+        bottom: Vec3::new(1.0, 1.0, 1.0),
+        top: Vec3::new(0.5, 0.7, 1.0),
+    };
+    pub fn color(&self, r: &Ray) -> Vec3 {
+        match self {
+            Environment::Gradient { bottom, top } => {
+                let unit_direction = r.direction().unit_vector();
+                let a = 0.5 * (unit_direction.y() + 1.0);
+                (1.0 - a) * bottom + a * top
+            }
+            Environment::Solid { color } => *color,
+        }
+    }
+}
+impl Default for Environment {
+    fn default() -> Self {
+        Self::SKY
+    }
+}
+
+/// Everything that can be rendered: the shapes and what surrounds them.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct World {
+    #[serde(default)]
     shapes: Vec<Shape>,
+    #[serde(default)]
+    environment: Environment,
 }
 impl World {
     pub const fn new() -> Self {
-        Self { shapes: Vec::new() }
+        Self {
+            shapes: Vec::new(),
+            environment: Environment::SKY,
+        }
+    }
+
+    pub const fn set_environment(&mut self, environment: Environment) {
+        self.environment = environment;
+    }
+
+    pub fn shapes(&self) -> &[Shape] {
+        &self.shapes
+    }
+
+    pub const fn environment(&self) -> &Environment {
+        &self.environment
     }
 
     pub fn add_shape(&mut self, s: impl Into<Shape>) {
@@ -23,20 +84,30 @@ impl Default for World {
 }
 impl<'a> Hittable for SharedWorld<'a> {
     fn hit(&self, r: &crate::ray::Ray) -> Option<crate::hittable::Hit> {
+        self.hit_with_index(r).map(|(_, hit)| hit)
+    }
+}
+impl<'a> SharedWorld<'a> {
+    /// The closest hit together with the index of the shape that was hit.
+    pub fn hit_with_index(&self, r: &crate::ray::Ray) -> Option<(usize, crate::hittable::Hit)> {
         self.shapes
             .iter()
-            .flat_map(|s| s.hit(r))
-            .min_by(|a, b| a.t.total_cmp(&b.t))
+            .enumerate()
+            .flat_map(|(i, s)| s.hit(r).map(|hit| (i, hit)))
+            .min_by(|(_, a), (_, b)| a.t.total_cmp(&b.t))
     }
 }
 impl<'a> From<&'a World> for SharedWorld<'a> {
     fn from(value: &'a World) -> Self {
         SharedWorld {
             shapes: &value.shapes,
+            environment: &value.environment,
         }
     }
 }
 
+/// A borrowed view of a [`World`] for the render threads.
 pub struct SharedWorld<'a> {
     pub shapes: &'a [Shape],
+    pub environment: &'a Environment,
 }